@@ -0,0 +1,84 @@
+//! Compares allocations made while reading many request bodies with and without a `BufferPool`.
+//!
+//! Run with `cargo bench -p cgi-rs --bench buffer_pool`.
+
+use cgi_rs::body::BufferPool;
+use cgi_rs::CGIRequest;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const REQUESTS: usize = 10_000;
+const BODY_SIZE: usize = 64 * 1024;
+
+struct CountingAllocator;
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+async fn run(name: &str, pool: Option<&BufferPool>) {
+    let body = vec![b'x'; BODY_SIZE];
+    let env = HashMap::from([
+        ("REQUEST_METHOD", "POST".to_string()),
+        ("CONTENT_LENGTH", BODY_SIZE.to_string()),
+    ]);
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let started = Instant::now();
+
+    for _ in 0..REQUESTS {
+        let request = match pool {
+            Some(pool) => CGIRequest::<Full<Bytes>>::from_source_with_reader_and_pool(
+                env.clone(),
+                &body[..],
+                pool,
+            ),
+            None => CGIRequest::<Full<Bytes>>::from_source_with_reader(env.clone(), &body[..]),
+        };
+        let bytes = request
+            .unwrap()
+            .request_body
+            .collect()
+            .await
+            .unwrap()
+            .to_bytes();
+        assert_eq!(bytes.len(), BODY_SIZE);
+        if let Some(pool) = pool {
+            pool.recycle(bytes);
+        }
+    }
+
+    let elapsed = started.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes;
+    println!(
+        "{:<12} {:>8} allocations {:>12} bytes allocated {:>10.2?}",
+        name, allocations, allocated_bytes, elapsed
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    println!("Reading {} request bodies of {} bytes", REQUESTS, BODY_SIZE);
+    run("unpooled", None).await;
+    run("pooled", Some(&BufferPool::default())).await;
+}
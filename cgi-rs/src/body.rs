@@ -0,0 +1,341 @@
+use crate::{error, CGIError};
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use snafu::ResultExt;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+
+// The maximum number of bytes read from the underlying reader for a single frame.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// A request body which lazily reads up to a fixed number of bytes from a reader.
+///
+/// Each poll performs at most one read of up to 16KiB, so nothing is read from the underlying reader (typically stdin)
+/// until the consumer of the body asks for the next frame.
+///
+/// ```rust
+/// use cgi_rs::body::ReaderBody;
+/// use http_body_util::BodyExt;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let body = ReaderBody::new(&b"hello world"[..], 5);
+/// let bytes = body.collect().await.unwrap().to_bytes();
+/// assert_eq!(bytes, "hello");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReaderBody<R> {
+    reader: R,
+    remaining: usize,
+}
+
+impl<R> ReaderBody<R>
+where
+    R: Read,
+{
+    /// Creates a body which reads exactly `length` bytes from `reader`.
+    pub fn new(reader: R, length: usize) -> Self {
+        ReaderBody {
+            reader,
+            remaining: length,
+        }
+    }
+}
+
+impl<R> Body for ReaderBody<R>
+where
+    R: Read + Unpin,
+{
+    type Data = Bytes;
+    type Error = CGIError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let mut buffer = vec![0u8; this.remaining.min(CHUNK_SIZE)];
+        let read = loop {
+            match this.reader.read(&mut buffer) {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+
+        let result = read
+            .and_then(|n| match n {
+                0 => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                n => Ok(n),
+            })
+            .context(error::ReadRequestBodySnafu)
+            .map(|n| {
+                this.remaining -= n;
+                buffer.truncate(n);
+                Frame::data(Bytes::from(buffer))
+            });
+
+        Poll::Ready(Some(result))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining as u64)
+    }
+}
+
+/// A response body which streams up to a fixed number of bytes from an [`AsyncRead`](tokio::io::AsyncRead).
+///
+/// If the reader fails, or reaches EOF before `length` bytes (as [`io::ErrorKind::UnexpectedEof`]), the body ends with
+/// that error. The response headers are already written by then, so the body is truncated.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncReaderBody<R> {
+    reader: R,
+    remaining: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncReaderBody<R>
+where
+    R: tokio::io::AsyncRead,
+{
+    /// Creates a body which reads `length` bytes from `reader`.
+    pub fn new(reader: R, length: usize) -> Self {
+        AsyncReaderBody {
+            reader,
+            remaining: length,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R> Body for AsyncReaderBody<R>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let mut buffer = vec![0u8; this.remaining.min(CHUNK_SIZE)];
+        let mut read_buf = tokio::io::ReadBuf::new(&mut buffer);
+        match Pin::new(&mut this.reader).poll_read(cx, &mut read_buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(())) if read_buf.filled().is_empty() => {
+                this.remaining = 0;
+                Poll::Ready(Some(Err(io::ErrorKind::UnexpectedEof.into())))
+            }
+            Poll::Ready(Ok(())) => {
+                let n = read_buf.filled().len();
+                this.remaining -= n;
+                buffer.truncate(n);
+                Poll::Ready(Some(Ok(Frame::data(Bytes::from(buffer)))))
+            }
+            Poll::Ready(Err(e)) => {
+                this.remaining = 0;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let mut hint = SizeHint::new();
+        hint.set_upper(self.remaining as u64);
+        hint
+    }
+}
+
+// Stdin as an `AsyncRead`, read on a thread of its own rather than on tokio's blocking pool. A read there can't be
+// cancelled, so a client stalling mid-upload would keep the runtime from shutting down after a body timeout, while
+// this thread is simply left behind when the process exits.
+#[cfg(feature = "tokio")]
+pub(crate) struct AsyncStdin {
+    // How many bytes the next read asks for. Reads are only made on request, so no more of stdin is consumed than is
+    // asked for.
+    requests: std::sync::mpsc::Sender<usize>,
+    reads: tokio::sync::mpsc::UnboundedReceiver<io::Result<Vec<u8>>>,
+    reading: bool,
+    // What the last read returned beyond the space in the caller's buffer.
+    unread: Vec<u8>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncStdin {
+    pub(crate) fn new() -> Self {
+        let (requests, pending) = std::sync::mpsc::channel::<usize>();
+        let (results, reads) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            let mut stdin = io::stdin();
+            for len in pending {
+                let mut buffer = vec![0u8; len];
+                let read = loop {
+                    match stdin.read(&mut buffer) {
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        result => break result,
+                    }
+                };
+                let result = read.map(|n| {
+                    buffer.truncate(n);
+                    buffer
+                });
+                if results.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+
+        AsyncStdin {
+            requests,
+            reads,
+            reading: false,
+            unread: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for AsyncStdin {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.unread.is_empty() {
+            if buf.remaining() == 0 {
+                return Poll::Ready(Ok(()));
+            }
+            if !this.reading {
+                let len = buf.remaining().min(CHUNK_SIZE);
+                if this.requests.send(len).is_err() {
+                    return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+                }
+                this.reading = true;
+            }
+            this.unread = match this.reads.poll_recv(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(read)) => {
+                    this.reading = false;
+                    read?
+                }
+                Poll::Ready(None) => return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+            };
+        }
+
+        let n = this.unread.len().min(buf.remaining());
+        buf.put_slice(&this.unread[..n]);
+        this.unread.drain(..n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A pool of buffers that request bodies are read into, so a long-running process (such as a FastCGI loop) reuses
+/// allocations across requests instead of allocating a fresh buffer for each body.
+///
+/// Pass the pool to [`CGIRequest::from_env_with_pool`](crate::CGIRequest::from_env_with_pool), then hand the
+/// collected body back with [`BufferPool::recycle`] once the request is handled. Buffers are cleared before they are
+/// reused, and at most `max_buffers` are kept.
+///
+/// ```rust
+/// use cgi_rs::body::BufferPool;
+/// use hyper::body::Bytes;
+///
+/// let pool = BufferPool::new(4);
+/// let mut buffer = pool.take();
+/// buffer.extend_from_slice(b"secret request body");
+/// let capacity = buffer.capacity();
+/// pool.give_back(buffer);
+/// assert_eq!(pool.len(), 1);
+///
+/// let buffer = pool.take();
+/// assert!(buffer.is_empty());
+/// assert_eq!(buffer.capacity(), capacity);
+///
+/// // A body that is still shared can't be reused.
+/// let body = Bytes::from(buffer);
+/// let shared = body.clone();
+/// pool.recycle(body);
+/// assert_eq!(pool.len(), 0);
+/// pool.recycle(shared);
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<Vec<u8>>>,
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool which keeps at most `max_buffers` buffers.
+    pub fn new(max_buffers: usize) -> Self {
+        BufferPool {
+            buffers: Mutex::new(Vec::with_capacity(max_buffers)),
+            max_buffers,
+        }
+    }
+
+    /// Takes an empty buffer from the pool, or allocates a new one if the pool is empty.
+    pub fn take(&self) -> Vec<u8> {
+        self.buffers().pop().unwrap_or_default()
+    }
+
+    /// Clears `buffer` and returns it to the pool, dropping it if the pool is full.
+    pub fn give_back(&self, mut buffer: Vec<u8>) {
+        buffer.clear();
+        let mut buffers = self.buffers();
+        if buffers.len() < self.max_buffers && buffer.capacity() > 0 {
+            buffers.push(buffer);
+        }
+    }
+
+    /// Returns the allocation behind a collected request body to the pool, if nothing else still references it.
+    pub fn recycle(&self, body: Bytes) {
+        if let Ok(body) = body.try_into_mut() {
+            self.give_back(body.into());
+        }
+    }
+
+    /// Returns the number of buffers currently in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers().len()
+    }
+
+    /// Returns `true` if the pool holds no buffers.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn buffers(&self) -> std::sync::MutexGuard<'_, Vec<Vec<u8>>> {
+        // The buffers are always left in a valid state, so a panic while the lock was held doesn't matter.
+        self.buffers
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        BufferPool::new(16)
+    }
+}
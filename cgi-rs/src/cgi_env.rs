@@ -0,0 +1,109 @@
+use crate::{EnvSource, MetaVariable, MetaVariableKind, ProcessEnv, Result};
+use std::net::IpAddr;
+
+/// A snapshot of the common CGI meta-variables, read from the environment once.
+///
+/// `REQUEST_METHOD` is required; every other variable is optional and `None` when unset. Values which are set but
+/// can't be parsed (non-UTF-8 text, a non-numeric port, and so on) are reported as errors rather than ignored.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use cgi_rs::CgiEnv;
+///
+/// let env = HashMap::from([
+///     ("REQUEST_METHOD", "GET"),
+///     ("SCRIPT_NAME", "/cgi-bin/app"),
+///     ("SERVER_PORT", "8080"),
+///     ("SERVER_ADDR", "[2001:db8::1]"),
+///     ("REMOTE_ADDR", "192.0.2.1"),
+/// ]);
+///
+/// let cgi_env = CgiEnv::from_source(&env).unwrap();
+/// assert_eq!(cgi_env.request_method, "GET");
+/// assert_eq!(cgi_env.script_name.as_deref(), Some("/cgi-bin/app"));
+/// assert_eq!(cgi_env.server_port, Some(8080));
+/// assert_eq!(cgi_env.server_addr, Some("2001:db8::1".parse().unwrap()));
+/// assert_eq!(cgi_env.remote_addr, Some("192.0.2.1".parse().unwrap()));
+/// assert_eq!(cgi_env.path_info, None);
+///
+/// let env = HashMap::from([("SCRIPT_NAME", "/cgi-bin/app")]);
+/// assert!(matches!(
+///     CgiEnv::from_source(&env),
+///     Err(cgi_rs::CGIError::MetaVariableNotSet { .. })
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgiEnv {
+    pub request_method: String,
+    pub request_uri: Option<String>,
+    pub script_name: Option<String>,
+    pub path_info: Option<String>,
+    pub path_translated: Option<String>,
+    pub query_string: Option<String>,
+    pub content_type: Option<String>,
+    pub content_length: Option<usize>,
+    pub server_name: Option<String>,
+    pub server_port: Option<u16>,
+    pub server_addr: Option<IpAddr>,
+    pub server_protocol: Option<String>,
+    pub server_software: Option<String>,
+    pub gateway_interface: Option<String>,
+    pub remote_addr: Option<IpAddr>,
+    pub remote_port: Option<u16>,
+    pub remote_host: Option<String>,
+    pub auth_type: Option<String>,
+    pub remote_user: Option<String>,
+}
+
+impl CgiEnv {
+    /// Reads the meta-variables from the process environment.
+    pub fn from_env() -> Result<CgiEnv> {
+        Self::from_source(&ProcessEnv)
+    }
+
+    /// Reads the meta-variables from `source`.
+    pub fn from_source<E: EnvSource + ?Sized>(source: &E) -> Result<CgiEnv> {
+        let string = |kind: MetaVariableKind| {
+            kind.from_source(source)
+                .map(|var| var.as_str().map(str::to_string))
+                .transpose()
+        };
+        let parsed = |kind: MetaVariableKind, parse: fn(&MetaVariable) -> Result<_>| {
+            kind.from_source(source).map(|var| parse(&var)).transpose()
+        };
+
+        Ok(CgiEnv {
+            request_method: MetaVariableKind::RequestMethod
+                .try_from_source(source)?
+                .as_str()?
+                .to_string(),
+            request_uri: string(MetaVariableKind::RequestUri)?,
+            script_name: string(MetaVariableKind::ScriptName)?,
+            path_info: string(MetaVariableKind::PathInfo)?,
+            path_translated: string(MetaVariableKind::PathTranslated)?,
+            query_string: string(MetaVariableKind::QueryString)?,
+            content_type: string(MetaVariableKind::ContentType)?,
+            content_length: MetaVariableKind::ContentLength
+                .from_source(source)
+                .map(|var| var.parse_content_length())
+                .transpose()?,
+            server_name: string(MetaVariableKind::ServerName)?,
+            server_port: parsed(MetaVariableKind::ServerPort, MetaVariable::parse_port)?,
+            server_addr: MetaVariableKind::ServerAddr
+                .from_source(source)
+                .map(|var| var.parse_ip_addr())
+                .transpose()?,
+            server_protocol: string(MetaVariableKind::ServerProtocol)?,
+            server_software: string(MetaVariableKind::ServerSoftware)?,
+            gateway_interface: string(MetaVariableKind::GatewayInterface)?,
+            remote_addr: MetaVariableKind::RemoteAddr
+                .from_source(source)
+                .map(|var| var.parse_ip_addr())
+                .transpose()?,
+            remote_port: parsed(MetaVariableKind::RemotePort, MetaVariable::parse_port)?,
+            remote_host: string(MetaVariableKind::RemoteHost)?,
+            auth_type: string(MetaVariableKind::AuthType)?,
+            remote_user: string(MetaVariableKind::RemoteUser)?,
+        })
+    }
+}
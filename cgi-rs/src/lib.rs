@@ -4,7 +4,7 @@
 //!
 //! Current limitations:
 //! * Only provides the needed utilities to create CGI scripts, not CGI servers.
-//! * Only "Document"-type responses are supported.
+//! * Only "Document" and redirect responses are supported.
 //! * Only a subset of the CGI environment variables are hoisted into Requests.
 //! * Does not support Windows.
 //!
@@ -39,18 +39,25 @@
 //! assert_eq!(method.as_str().unwrap(), "GET");
 //! ```
 
-use snafu::OptionExt;
+use snafu::{OptionExt, ResultExt};
+use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::hash::{BuildHasher, Hash};
+use std::net::IpAddr;
 // We need to be able to access environment variables as octet sequences.
 // While this works, it prevents us from supporting Windows.
 use std::os::unix::ffi::OsStrExt;
 
+pub mod body;
+pub mod cgi_env;
 pub mod request;
 pub mod response;
 
+pub use cgi_env::CgiEnv;
 pub use request::CGIRequest;
-pub use response::CGIResponse;
+pub use response::{CGIResponse, CGIResponseBuilder, ResponseBody, WriteOptions};
 
 /// Contains the value of a CGI "meta-variable".
 ///
@@ -80,6 +87,65 @@ impl MetaVariable {
     pub fn as_bytes(&self) -> &[u8] {
         self.value.as_bytes()
     }
+
+    /// Parses the value as a content length, e.g. of `CONTENT_LENGTH`, tolerating surrounding whitespace and a leading
+    /// `+`.
+    ///
+    /// Returns [`CGIError::InvalidContentLength`] if the value isn't a non-negative integer.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use cgi_rs::{CGIError, MetaVariableKind};
+    ///
+    /// let env = HashMap::from([
+    ///     ("CONTENT_LENGTH", " 42"),
+    ///     ("SERVER_PORT", "abc"),
+    ///     ("REMOTE_ADDR", "[::1]"),
+    ///     ("SERVER_ADDR", "localhost"),
+    /// ]);
+    /// let var = |kind: MetaVariableKind| kind.try_from_source(&env).unwrap();
+    ///
+    /// assert_eq!(var(MetaVariableKind::ContentLength).parse_content_length().unwrap(), 42);
+    /// assert!(matches!(
+    ///     var(MetaVariableKind::ServerPort).parse_port(),
+    ///     Err(CGIError::InvalidPort { kind: MetaVariableKind::ServerPort, .. })
+    /// ));
+    /// let remote_addr = var(MetaVariableKind::RemoteAddr).parse_ip_addr().unwrap();
+    /// assert_eq!(remote_addr, "::1".parse::<std::net::IpAddr>().unwrap());
+    /// assert!(matches!(var(MetaVariableKind::ServerAddr).parse_ip_addr(), Err(CGIError::InvalidIpAddr { .. })));
+    /// ```
+    pub fn parse_content_length(&self) -> Result<usize> {
+        self.as_str()
+            // Some gateways pad the value with whitespace or prefix it with a sign.
+            .map(|s| s.trim().trim_start_matches('+'))
+            .and_then(|s| s.parse().context(error::InvalidContentLengthSnafu))
+    }
+
+    /// Parses the value as a port number, e.g. of `SERVER_PORT` or `REMOTE_PORT`.
+    ///
+    /// Returns [`CGIError::InvalidPort`] if the value isn't an integer between 0 and 65535.
+    pub fn parse_port(&self) -> Result<u16> {
+        self.as_str().and_then(|s| {
+            s.trim()
+                .parse()
+                .context(error::InvalidPortSnafu { kind: self.kind })
+        })
+    }
+
+    /// Parses the value as an IP address, e.g. of `REMOTE_ADDR` or `SERVER_ADDR`. IPv6 addresses may be wrapped in
+    /// brackets.
+    ///
+    /// Returns [`CGIError::InvalidIpAddr`] if the value isn't an IP address.
+    pub fn parse_ip_addr(&self) -> Result<IpAddr> {
+        self.as_str().and_then(|s| {
+            // Some servers wrap IPv6 literals in brackets, e.g. "[::1]"
+            s.trim()
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse()
+                .context(error::InvalidIpAddrSnafu { kind: self.kind })
+        })
+    }
 }
 
 // https://datatracker.ietf.org/doc/html/rfc3875#section-4.1
@@ -100,6 +166,7 @@ pub enum MetaVariableKind {
     ScriptName,
     ServerName,
     ServerPort,
+    ServerAddr,
     ServerProtocol,
     ServerSoftware,
 
@@ -108,19 +175,160 @@ pub enum MetaVariableKind {
     HttpHost,
     HttpUserAgent,
     HttpAccept,
+    HttpAcceptEncoding,
+    HttpAcceptLanguage,
+    HttpReferer,
+    HttpVia,
+    /// The `X-Forwarded-Host` header, the host a client asked a reverse proxy for. Only used for the request's host
+    /// when the request comes from a proxy set with
+    /// [`CGIRequest::with_trusted_proxies`](crate::CGIRequest::with_trusted_proxies).
+    HttpXForwardedHost,
+    /// The `Authorization` header, hoisted into requests.
+    ///
+    /// Many servers withhold it from CGI scripts unless configured otherwise (e.g. Apache's `CGIPassAuth On`), so a
+    /// missing header may mean the server stripped it rather than the client not sending it.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::{body::Bytes, Request};
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "POST"),
+    ///     ("REQUEST_URI", "/api/items"),
+    ///     ("CONTENT_LENGTH", "2"),
+    ///     ("HTTP_AUTHORIZATION", "Bearer abc123"),
+    /// ]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"{}"[..])
+    ///     .and_then(Request::try_from)
+    ///     .unwrap();
+    /// assert_eq!(request.headers()["Authorization"], "Bearer abc123");
+    /// ```
+    HttpAuthorization,
     HttpCookie,
     ServerSignature,
     DocumentRoot,
     RequestScheme,
+    Https,
     ContextDocumentRoot,
     ServerAdmin,
     ScriptFilename,
     RemotePort,
     RequestUri,
+    HttpSaveData,
+    HttpCacheControl,
+    HttpTe,
+    HttpAcceptCharset,
+    HttpIfMatch,
+    HttpIfNoneMatch,
+    HttpIfModifiedSince,
+    HttpIfUnmodifiedSince,
+    HttpSecFetchSite,
+    HttpSecFetchMode,
+    HttpSecFetchDest,
+    HttpSecFetchUser,
+    HttpSecChUa,
+    HttpSecChUaMobile,
+    HttpSecChUaPlatform,
+    HttpViewportWidth,
+    HttpDpr,
+    HttpTraceparent,
+    HttpTracestate,
+
+    /// Any other variable, by name, such as `REDIRECT_STATUS` or `SSL_CLIENT_CERT`.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use cgi_rs::MetaVariableKind;
+    ///
+    /// let redirect_status = MetaVariableKind::Other("REDIRECT_STATUS");
+    /// assert_eq!(redirect_status.to_string(), "REDIRECT_STATUS");
+    ///
+    /// let env = HashMap::from([("REDIRECT_STATUS", "200")]);
+    /// assert_eq!(redirect_status.try_from_source(&env).unwrap().as_str().unwrap(), "200");
+    /// assert!(MetaVariableKind::Other("SSL_CLIENT_CERT").from_source(&env).is_none());
+    /// ```
+    Other(&'static str),
 }
 
 impl MetaVariableKind {
-    fn as_str(&self) -> &'static str {
+    const ALL: &'static [MetaVariableKind] = &[
+        MetaVariableKind::AuthType,
+        MetaVariableKind::ContentLength,
+        MetaVariableKind::ContentType,
+        MetaVariableKind::GatewayInterface,
+        MetaVariableKind::PathInfo,
+        MetaVariableKind::PathTranslated,
+        MetaVariableKind::QueryString,
+        MetaVariableKind::RemoteAddr,
+        MetaVariableKind::RemoteHost,
+        MetaVariableKind::RequestIdent,
+        MetaVariableKind::RemoteUser,
+        MetaVariableKind::RequestMethod,
+        MetaVariableKind::ScriptName,
+        MetaVariableKind::ServerName,
+        MetaVariableKind::ServerPort,
+        MetaVariableKind::ServerAddr,
+        MetaVariableKind::ServerProtocol,
+        MetaVariableKind::ServerSoftware,
+        MetaVariableKind::UniqueID,
+        MetaVariableKind::HttpHost,
+        MetaVariableKind::HttpUserAgent,
+        MetaVariableKind::HttpAccept,
+        MetaVariableKind::HttpAcceptEncoding,
+        MetaVariableKind::HttpAcceptLanguage,
+        MetaVariableKind::HttpReferer,
+        MetaVariableKind::HttpVia,
+        MetaVariableKind::HttpXForwardedHost,
+        MetaVariableKind::HttpAuthorization,
+        MetaVariableKind::HttpCookie,
+        MetaVariableKind::ServerSignature,
+        MetaVariableKind::DocumentRoot,
+        MetaVariableKind::RequestScheme,
+        MetaVariableKind::Https,
+        MetaVariableKind::ContextDocumentRoot,
+        MetaVariableKind::ServerAdmin,
+        MetaVariableKind::ScriptFilename,
+        MetaVariableKind::RemotePort,
+        MetaVariableKind::RequestUri,
+        MetaVariableKind::HttpSaveData,
+        MetaVariableKind::HttpCacheControl,
+        MetaVariableKind::HttpTe,
+        MetaVariableKind::HttpAcceptCharset,
+        MetaVariableKind::HttpIfMatch,
+        MetaVariableKind::HttpIfNoneMatch,
+        MetaVariableKind::HttpIfModifiedSince,
+        MetaVariableKind::HttpIfUnmodifiedSince,
+        MetaVariableKind::HttpSecFetchSite,
+        MetaVariableKind::HttpSecFetchMode,
+        MetaVariableKind::HttpSecFetchDest,
+        MetaVariableKind::HttpSecFetchUser,
+        MetaVariableKind::HttpSecChUa,
+        MetaVariableKind::HttpSecChUaMobile,
+        MetaVariableKind::HttpSecChUaPlatform,
+        MetaVariableKind::HttpViewportWidth,
+        MetaVariableKind::HttpDpr,
+        MetaVariableKind::HttpTraceparent,
+        MetaVariableKind::HttpTracestate,
+    ];
+
+    /// Returns every kind except [`MetaVariableKind::Other`], in declaration order.
+    ///
+    /// ```rust
+    /// use std::collections::HashSet;
+    /// use cgi_rs::MetaVariableKind;
+    ///
+    /// let all = MetaVariableKind::all();
+    /// assert_eq!(all.len(), 57);
+    /// assert_eq!(all.iter().collect::<HashSet<_>>().len(), all.len());
+    /// assert!(!all.iter().any(|kind| matches!(kind, MetaVariableKind::Other(_))));
+    /// ```
+    pub fn all() -> &'static [MetaVariableKind] {
+        Self::ALL
+    }
+
+    /// Returns the name of the environment variable, e.g. `"REQUEST_METHOD"`.
+    pub fn as_str(&self) -> &'static str {
         match self {
             MetaVariableKind::AuthType => "AUTH_TYPE",
             MetaVariableKind::ContentLength => "CONTENT_LENGTH",
@@ -137,42 +345,172 @@ impl MetaVariableKind {
             MetaVariableKind::ScriptName => "SCRIPT_NAME",
             MetaVariableKind::ServerName => "SERVER_NAME",
             MetaVariableKind::ServerPort => "SERVER_PORT",
+            MetaVariableKind::ServerAddr => "SERVER_ADDR",
             MetaVariableKind::ServerProtocol => "SERVER_PROTOCOL",
             MetaVariableKind::ServerSoftware => "SERVER_SOFTWARE",
             MetaVariableKind::UniqueID => "UNIQUE_ID",
             MetaVariableKind::HttpHost => "HTTP_HOST",
             MetaVariableKind::HttpUserAgent => "HTTP_USER_AGENT",
             MetaVariableKind::HttpAccept => "HTTP_ACCEPT",
+            MetaVariableKind::HttpAcceptEncoding => "HTTP_ACCEPT_ENCODING",
+            MetaVariableKind::HttpAcceptLanguage => "HTTP_ACCEPT_LANGUAGE",
+            MetaVariableKind::HttpReferer => "HTTP_REFERER",
+            MetaVariableKind::HttpVia => "HTTP_VIA",
+            MetaVariableKind::HttpXForwardedHost => "HTTP_X_FORWARDED_HOST",
+            MetaVariableKind::HttpAuthorization => "HTTP_AUTHORIZATION",
             MetaVariableKind::ServerSignature => "SERVER_SIGNATURE",
             MetaVariableKind::DocumentRoot => "DOCUMENT_ROOT",
             MetaVariableKind::RequestScheme => "REQUEST_SCHEME",
+            MetaVariableKind::Https => "HTTPS",
             MetaVariableKind::ContextDocumentRoot => "CONTEXT_DOCUMENT_ROOT",
             MetaVariableKind::ServerAdmin => "SERVER_ADMIN",
             MetaVariableKind::ScriptFilename => "SCRIPT_FILENAME",
             MetaVariableKind::RemotePort => "REMOTE_PORT",
             MetaVariableKind::RequestUri => "REQUEST_URI",
-            MetaVariableKind::HttpCookie => "HTTP_COOKIE"
+            MetaVariableKind::HttpCookie => "HTTP_COOKIE",
+            MetaVariableKind::HttpSaveData => "HTTP_SAVE_DATA",
+            MetaVariableKind::HttpCacheControl => "HTTP_CACHE_CONTROL",
+            MetaVariableKind::HttpTe => "HTTP_TE",
+            MetaVariableKind::HttpAcceptCharset => "HTTP_ACCEPT_CHARSET",
+            MetaVariableKind::HttpIfMatch => "HTTP_IF_MATCH",
+            MetaVariableKind::HttpIfNoneMatch => "HTTP_IF_NONE_MATCH",
+            MetaVariableKind::HttpIfModifiedSince => "HTTP_IF_MODIFIED_SINCE",
+            MetaVariableKind::HttpIfUnmodifiedSince => "HTTP_IF_UNMODIFIED_SINCE",
+            MetaVariableKind::HttpSecFetchSite => "HTTP_SEC_FETCH_SITE",
+            MetaVariableKind::HttpSecFetchMode => "HTTP_SEC_FETCH_MODE",
+            MetaVariableKind::HttpSecFetchDest => "HTTP_SEC_FETCH_DEST",
+            MetaVariableKind::HttpSecFetchUser => "HTTP_SEC_FETCH_USER",
+            MetaVariableKind::HttpSecChUa => "HTTP_SEC_CH_UA",
+            MetaVariableKind::HttpSecChUaMobile => "HTTP_SEC_CH_UA_MOBILE",
+            MetaVariableKind::HttpSecChUaPlatform => "HTTP_SEC_CH_UA_PLATFORM",
+            MetaVariableKind::HttpViewportWidth => "HTTP_VIEWPORT_WIDTH",
+            MetaVariableKind::HttpDpr => "HTTP_DPR",
+            MetaVariableKind::HttpTraceparent => "HTTP_TRACEPARENT",
+            MetaVariableKind::HttpTracestate => "HTTP_TRACESTATE",
+            MetaVariableKind::Other(name) => name,
         }
     }
 
     pub fn from_env(&self) -> Option<MetaVariable> {
-        env::var_os(self.as_str()).map(|value| MetaVariable { kind: *self, value })
+        self.from_source(&ProcessEnv)
     }
 
     pub fn try_from_env(&self) -> Result<MetaVariable> {
+        self.try_from_source(&ProcessEnv)
+    }
+
+    pub fn from_source<E: EnvSource + ?Sized>(&self, source: &E) -> Option<MetaVariable> {
+        source
+            .get(self.as_str())
+            .map(|value| MetaVariable { kind: *self, value })
+    }
+
+    pub fn try_from_source<E: EnvSource + ?Sized>(&self, source: &E) -> Result<MetaVariable> {
         let kind = *self;
-        env::var_os(self.as_str())
-            .map(|value| MetaVariable { kind, value })
+        self.from_source(source)
             .context(error::MetaVariableNotSetSnafu { kind })
     }
 }
 
+/// A source of CGI meta-variables.
+///
+/// Requests normally read meta-variables from the process environment via [`ProcessEnv`], but any source can be
+/// injected, e.g. a `HashMap` in tests, or when handling several requests in one process.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use cgi_rs::MetaVariableKind;
+///
+/// let env = HashMap::from([("REQUEST_METHOD", "GET")]);
+/// let method = MetaVariableKind::RequestMethod.try_from_source(&env).unwrap();
+///
+/// assert_eq!(method.as_str().unwrap(), "GET");
+/// ```
+pub trait EnvSource: Send + Sync {
+    /// Returns the value of the variable named `key`, if set.
+    fn get(&self, key: &str) -> Option<OsString>;
+}
+
+/// Returns every known meta-variable which is set in the process environment, e.g. to dump them for debugging.
+///
+/// Variables without a [`MetaVariableKind`] of their own, which could only be read as [`MetaVariableKind::Other`],
+/// aren't included.
+///
+/// ```rust
+/// use cgi_rs::MetaVariableKind;
+///
+/// std::env::set_var("SERVER_NAME", "example.com");
+/// std::env::remove_var("SERVER_PORT");
+///
+/// let present: Vec<_> = cgi_rs::present_meta_variables().map(|var| var.kind).collect();
+/// assert!(present.contains(&MetaVariableKind::ServerName));
+/// assert!(!present.contains(&MetaVariableKind::ServerPort));
+///
+/// for var in cgi_rs::present_meta_variables() {
+///     eprintln!("{}={:?}", var.kind, var.value);
+/// }
+/// ```
+pub fn present_meta_variables() -> impl Iterator<Item = MetaVariable> {
+    MetaVariableKind::ALL
+        .iter()
+        .filter_map(MetaVariableKind::from_env)
+}
+
+/// Reads meta-variables from the process environment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessEnv;
+
+impl EnvSource for ProcessEnv {
+    fn get(&self, key: &str) -> Option<OsString> {
+        env::var_os(key)
+    }
+}
+
+impl<K, V, S> EnvSource for HashMap<K, V, S>
+where
+    K: Borrow<str> + Hash + Eq + Send + Sync,
+    V: AsRef<OsStr> + Send + Sync,
+    S: BuildHasher + Send + Sync,
+{
+    fn get(&self, key: &str) -> Option<OsString> {
+        HashMap::get(self, key).map(|value| value.as_ref().to_os_string())
+    }
+}
+
 impl std::fmt::Display for MetaVariableKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.as_str())
     }
 }
 
+/// Looks up the known kind with the given variable name, the reverse of its `Display` impl.
+///
+/// Names without a kind of their own fail with [`CGIError::UnknownMetaVariable`]; use [`MetaVariableKind::Other`] to
+/// read those.
+///
+/// ```rust
+/// use cgi_rs::{CGIError, MetaVariableKind};
+///
+/// for &kind in MetaVariableKind::all() {
+///     assert_eq!(kind.as_str().parse::<MetaVariableKind>().unwrap(), kind);
+/// }
+/// assert!(matches!(
+///     "REDIRECT_STATUS".parse::<MetaVariableKind>(),
+///     Err(CGIError::UnknownMetaVariable { .. })
+/// ));
+/// ```
+impl std::str::FromStr for MetaVariableKind {
+    type Err = CGIError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        MetaVariableKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.as_str() == name)
+            .context(error::UnknownMetaVariableSnafu { name })
+    }
+}
+
 pub mod error {
     use super::*;
     use snafu::Snafu;
@@ -186,9 +524,59 @@ pub mod error {
         #[snafu(display("Error fetching meta-varaible '{}' from environment: not set", kind))]
         MetaVariableNotSet { kind: MetaVariableKind },
 
+        #[snafu(display("'{}' is not a known meta-variable", name.escape_debug()))]
+        UnknownMetaVariable { name: String },
+
+        #[snafu(display(
+            "Unable to determine request URI: neither '{}' nor '{}' is set",
+            MetaVariableKind::RequestUri,
+            MetaVariableKind::ScriptName
+        ))]
+        CannotDetermineUri,
+
+        #[snafu(display("Request URI of {} bytes exceeds the limit of {} bytes", length, limit))]
+        UriTooLong { length: usize, limit: usize },
+
+        #[snafu(display("REQUEST_METHOD is set but empty"))]
+        EmptyMethod,
+
+        #[snafu(display("Request method '{}' is not a valid HTTP method", value.escape_debug()))]
+        InvalidMethod { value: String },
+
+        #[snafu(display("Request body has content type '{}', expected a urlencoded form", value.escape_debug()))]
+        NotAForm { value: String },
+
+        #[snafu(display("Request URI '{}' is malformed", value.escape_debug()))]
+        InvalidUri { value: String },
+
+        #[snafu(display("Host '{}' is malformed", value.escape_debug()))]
+        InvalidHost { value: String },
+
+        #[snafu(display("Quality value '{}' in header '{}' is malformed", value.escape_debug(), kind))]
+        InvalidQuality {
+            kind: MetaVariableKind,
+            value: String,
+        },
+
         #[snafu(display("Failed to parse content-length: {}", source))]
         InvalidContentLength { source: std::num::ParseIntError },
 
+        #[snafu(display("Failed to parse meta-variable '{}' as a port: {}", kind, source))]
+        InvalidPort {
+            kind: MetaVariableKind,
+            source: std::num::ParseIntError,
+        },
+
+        #[snafu(display(
+            "Failed to parse meta-variable '{}' as an IP address: {}",
+            kind,
+            source
+        ))]
+        InvalidIpAddr {
+            kind: MetaVariableKind,
+            source: std::net::AddrParseError,
+        },
+
         #[snafu(display(
             "Failed to parse meta-variable '{}' value '{}' as {}",
             kind,
@@ -201,15 +589,62 @@ pub mod error {
             encoding: &'static str,
         },
 
+        #[snafu(display(
+            "Request body of {} bytes exceeds the limit of {} bytes",
+            declared,
+            limit
+        ))]
+        ContentTooLarge { limit: usize, declared: usize },
+
+        #[snafu(display(
+            "Request body is longer than its declared CONTENT_LENGTH of {} bytes",
+            declared
+        ))]
+        ExcessRequestBody { declared: usize },
+
         #[snafu(display("Failed to read request body from stdin: {}", source))]
         ReadRequestBody { source: std::io::Error },
 
         #[snafu(display("Failed to parse request: {}", source))]
         RequestParse { source: hyper::http::Error },
 
+        #[snafu(display("Failed to open response body file '{}': {}", path.display(), source))]
+        OpenResponseFile {
+            path: std::path::PathBuf,
+            source: std::io::Error,
+        },
+
+        #[snafu(display("Failed to read response body: {}", source))]
+        ReadResponseBody { source: response::BoxError },
+
+        #[cfg(feature = "json")]
+        #[snafu(display("Failed to serialize JSON response body: {}", source))]
+        SerializeJson { source: serde_json::Error },
+
+        #[snafu(display("Failed to build response: {}", source))]
+        InvalidResponse { source: hyper::http::Error },
+
         #[snafu(display("Failed to gather response into buffer"))]
         BuildResponse,
 
+        #[snafu(display(
+            "Response header block of {} bytes exceeds the limit of {} bytes",
+            size,
+            limit
+        ))]
+        HeaderBlockTooLarge { size: usize, limit: usize },
+
+        #[snafu(display("Response header name '{}' is not a valid HTTP token", name.escape_debug()))]
+        InvalidHeaderName { name: String },
+
+        #[snafu(display("Value of response header '{}' contains a line break", name))]
+        InvalidHeaderValue { name: String },
+
+        #[snafu(display(
+            "Streamed response bodies must be written with write_streaming_response_to_output"
+        ))]
+        StreamingBody,
+
         #[snafu(display("Failed to write response: {}", source))]
         WriteResponse { source: std::io::Error },
     }
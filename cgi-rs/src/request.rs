@@ -1,62 +1,1999 @@
-use std::convert::Infallible;
-use crate::{error, CGIError, MetaVariable, MetaVariableKind, Result};
-use hyper::Request;
+#[cfg(feature = "tokio")]
+use crate::body::AsyncStdin;
+use crate::body::{BufferPool, ReaderBody};
+use crate::response::BoxError;
+use crate::{
+    error, CGIError, CGIResponse, EnvSource, MetaVariable, MetaVariableKind, ProcessEnv, Result,
+};
+use http_body_util::{BodyExt, Full};
 use hyper::body::{Body, Bytes};
-use snafu::ResultExt;
-use std::io::{stdin, Read};
-use http_body_util::combinators::BoxBody;
-use http_body_util::Full;
+use hyper::header::{HeaderName, LOCATION};
+use hyper::http::HeaderValue;
+use hyper::{Method, Request, StatusCode};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::io::{self, stdin, IsTerminal, Read, Stdin};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// The client's address and port, taken from `REMOTE_ADDR` and `REMOTE_PORT`.
+///
+/// [`Request::try_from`] inserts this as a request extension. If `REMOTE_PORT` is missing or isn't a valid port, the
+/// bare [`IpAddr`] is inserted instead.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use hyper::Request;
+/// # use hyper::body::Bytes;
+/// # use http_body_util::Full;
+/// use cgi_rs::{request::RemotePeer, CGIRequest};
+///
+/// let env = HashMap::from([
+///     ("REQUEST_METHOD", "GET"),
+///     ("REQUEST_URI", "/"),
+///     ("REMOTE_ADDR", "::1"),
+///     ("REMOTE_PORT", "54321"),
+/// ]);
+///
+/// let request: Request<Full<Bytes>> = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+///     .and_then(Request::try_from).unwrap();
+/// let peer = request.extensions().get::<RemotePeer>().unwrap();
+/// assert_eq!(peer.0, "[::1]:54321".parse().unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RemotePeer(pub SocketAddr);
+
+/// The authentication performed by the server, taken from `AUTH_TYPE` and `REMOTE_USER`.
+///
+/// [`Request::try_from`] inserts this as a request extension when either variable is set.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use hyper::Request;
+/// # use hyper::body::Bytes;
+/// # use http_body_util::Full;
+/// use cgi_rs::{request::CgiAuth, CGIRequest};
+///
+/// let env = HashMap::from([
+///     ("REQUEST_METHOD", "GET"),
+///     ("REQUEST_URI", "/"),
+///     ("AUTH_TYPE", "Basic"),
+///     ("REMOTE_USER", "alice"),
+/// ]);
+///
+/// let request: Request<Full<Bytes>> = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+///     .and_then(Request::try_from).unwrap();
+/// let auth = request.extensions().get::<CgiAuth>().unwrap();
+/// assert_eq!(auth.auth_type.as_deref(), Some("Basic"));
+/// assert_eq!(auth.remote_user.as_deref(), Some("alice"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgiAuth {
+    pub auth_type: Option<String>,
+    pub remote_user: Option<String>,
+}
+
+/// Records that stdin closed before the declared `CONTENT_LENGTH` was read.
+///
+/// [`Request::try_from`] inserts this as a request extension when the body is incomplete, so handlers can decide
+/// whether a partial body is acceptable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncatedBody {
+    /// The length declared by `CONTENT_LENGTH`.
+    pub declared: usize,
+    /// The number of bytes actually read.
+    pub received: usize,
+}
+
+/// The directives of a request's `Cache-Control` header, per RFC 9111 §5.2.1.
+///
+/// Unknown directives and directives with malformed values are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControlDirectives {
+    pub no_cache: bool,
+    pub no_store: bool,
+    pub no_transform: bool,
+    pub only_if_cached: bool,
+    /// `max-age`, in seconds.
+    pub max_age: Option<u64>,
+    /// `max-stale`, in seconds. `Some(None)` means the client accepts a stale response of any age.
+    pub max_stale: Option<Option<u64>>,
+    /// `min-fresh`, in seconds.
+    pub min_fresh: Option<u64>,
+}
+
+impl CacheControlDirectives {
+    fn parse(value: &str) -> Self {
+        let mut directives = CacheControlDirectives::default();
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name, Some(argument.trim().trim_matches('"'))),
+                None => (directive, None),
+            };
+            let seconds = argument.and_then(|argument| argument.parse().ok());
+
+            match name.trim().to_ascii_lowercase().as_str() {
+                "no-cache" => directives.no_cache = true,
+                "no-store" => directives.no_store = true,
+                "no-transform" => directives.no_transform = true,
+                "only-if-cached" => directives.only_if_cached = true,
+                "max-age" => directives.max_age = seconds,
+                "max-stale" if argument.is_none() => directives.max_stale = Some(None),
+                "max-stale" => directives.max_stale = seconds.map(Some),
+                "min-fresh" => directives.min_fresh = seconds,
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+/// The Fetch Metadata request headers (`Sec-Fetch-*`) sent by browsers to describe the context of a request.
+///
+/// Each field is `None` if its header is unset or isn't valid UTF-8, e.g. because the client predates Fetch Metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchMetadata {
+    /// `Sec-Fetch-Site`: `same-origin`, `same-site`, `cross-site` or `none`.
+    pub site: Option<String>,
+    /// `Sec-Fetch-Mode`, e.g. `navigate` or `cors`.
+    pub mode: Option<String>,
+    /// `Sec-Fetch-Dest`, e.g. `document` or `image`.
+    pub dest: Option<String>,
+    /// `Sec-Fetch-User`: `true` if the request was triggered by user activation.
+    pub user: Option<bool>,
+}
+
+/// The Client Hints request headers sent by browsers to let responses be tailored to the device.
+///
+/// Each field is `None` if its header is unset, isn't valid UTF-8 or fails to parse. Browsers only send most hints
+/// after a response has asked for them with `Accept-CH`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientHints {
+    /// `Sec-CH-UA`: the browser's brand list, e.g. `"Chromium";v="124", "Not-A.Brand";v="99"`.
+    pub ua: Option<String>,
+    /// `Sec-CH-UA-Mobile`: `true` if the browser prefers a mobile experience.
+    pub mobile: Option<bool>,
+    /// `Sec-CH-UA-Platform`, unquoted, e.g. `Android` or `Windows`.
+    pub platform: Option<String>,
+    /// `Viewport-Width`: the layout viewport width in CSS pixels.
+    pub viewport_width: Option<u32>,
+    /// `DPR`: the ratio of physical pixels to CSS pixels.
+    pub dpr: Option<f32>,
+}
+
+/// A W3C Trace Context, identifying the distributed trace a request is part of and the span that sent it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The `traceparent` format version, `0` for the current one.
+    pub version: u8,
+    /// The ID of the whole trace, never zero.
+    pub trace_id: u128,
+    /// The ID of the caller's span, never zero.
+    pub parent_id: u64,
+    /// The trace flags, of which bit 0 is `sampled`.
+    pub flags: u8,
+    /// The vendor-specific `tracestate` header, if any.
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Returns true if the caller may have recorded its span, so this request's spans should be recorded too.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+}
+
+// Parses a `traceparent` header: `version-trace_id-parent_id-flags` in lowercase hex, per W3C Trace Context §3.2.
+fn parse_traceparent(value: &str) -> Option<(u8, u128, u64, u8)> {
+    // Fields have a fixed number of digits, so the casts below can't truncate.
+    fn hex(field: &str, digits: usize) -> Option<u128> {
+        let is_hex = field.len() == digits
+            && field
+                .bytes()
+                .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'));
+        is_hex
+            .then(|| u128::from_str_radix(field, 16).ok())
+            .flatten()
+    }
+
+    let mut fields = value.trim().split('-');
+    let version = hex(fields.next()?, 2)? as u8;
+    let trace_id = hex(fields.next()?, 32)?;
+    let parent_id = hex(fields.next()?, 16)? as u64;
+    let flags = hex(fields.next()?, 2)? as u8;
+
+    // Version 255 is invalid, and version 0 has exactly four fields. Later versions may append more.
+    let valid = match version {
+        0 => fields.next().is_none(),
+        255 => false,
+        _ => true,
+    };
+    (valid && trace_id != 0 && parent_id != 0).then_some((version, trace_id, parent_id, flags))
+}
+
+/// The outcome of evaluating a request's conditional headers, per RFC 7232 §6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// The request should be handled normally.
+    None,
+    /// The client's cached representation is current, and should be answered with `304 Not Modified`.
+    NotModified,
+    /// A precondition failed, and the request should be answered with `412 Precondition Failed`.
+    PreconditionFailed,
+}
+
+impl Precondition {
+    /// Returns the empty `304` or `412` response for this outcome, or `None` if the request should be handled
+    /// normally.
+    pub fn response(self) -> Option<CGIResponse> {
+        match self {
+            Precondition::None => None,
+            Precondition::NotModified => Some(CGIResponse::new(StatusCode::NOT_MODIFIED)),
+            Precondition::PreconditionFailed => {
+                Some(CGIResponse::new(StatusCode::PRECONDITION_FAILED))
+            }
+        }
+    }
+}
+
+// Parses a structured-header boolean (RFC 8941 §3.3.6).
+fn parse_sf_boolean(value: &str) -> Option<bool> {
+    match value {
+        "?1" => Some(true),
+        "?0" => Some(false),
+        _ => None,
+    }
+}
+
+// Returns true if `etag` appears in the comma-separated list of entity tags, or the list is "*".
+pub(crate) fn etag_list_matches(list: &str, etag: &str, weak: bool) -> bool {
+    fn opaque(tag: &str) -> (bool, &str) {
+        let tag = tag.trim();
+        match tag.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        }
+    }
+    let (etag_is_weak, etag_opaque) = opaque(etag);
+
+    list.split(',').any(|candidate| {
+        if candidate.trim() == "*" {
+            return true;
+        }
+        let (candidate_is_weak, candidate_opaque) = opaque(candidate);
+        candidate_opaque == etag_opaque && (weak || !(candidate_is_weak || etag_is_weak))
+    })
+}
+
+/// How a request body is reconciled with `CONTENT_LENGTH` when the reader holds more data than declared.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use hyper::body::Bytes;
+/// # use http_body_util::{BodyExt, Full};
+/// use cgi_rs::{request::BodyLengthPolicy, CGIError, CGIRequest};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let read = |policy| {
+///     let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", "5")]);
+///     CGIRequest::<Full<Bytes>>::from_source_with_reader_and_policy(env, &b"hello world"[..], policy)
+/// };
+///
+/// let request = read(BodyLengthPolicy::Truncate).unwrap();
+/// assert_eq!(request.request_body.collect().await.unwrap().to_bytes(), "hello");
+///
+/// let request = read(BodyLengthPolicy::ReadAll).unwrap();
+/// assert_eq!(request.request_body.collect().await.unwrap().to_bytes(), "hello world");
+///
+/// assert!(matches!(
+///     read(BodyLengthPolicy::Strict),
+///     Err(CGIError::ExcessRequestBody { declared: 5 })
+/// ));
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodyLengthPolicy {
+    /// Fail with [`CGIError::ExcessRequestBody`] if data remains after `CONTENT_LENGTH` bytes.
+    ///
+    /// This reads past the declared body, so it blocks if the server keeps stdin open after sending the body.
+    Strict,
+    /// Read `CONTENT_LENGTH` bytes and ignore the rest.
+    #[default]
+    Truncate,
+    /// Read to EOF, ignoring `CONTENT_LENGTH`, up to the smaller of the body limit and 16MiB.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::BodyLengthPolicy, CGIRequest};
+    ///
+    /// // A declared length doesn't raise the limit or the buffer reserved for the body.
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "POST".to_string()),
+    ///     ("CONTENT_LENGTH", usize::MAX.to_string()),
+    /// ]);
+    /// let policy = BodyLengthPolicy::ReadAll;
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader_and_policy(env, &b"hello"[..], policy);
+    /// assert_eq!(request.unwrap().body_len(), 5);
+    /// ```
+    ReadAll,
+}
+
+/// How a request body is read, combining the limits the `from_env_with_*` constructors each set on their own.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use hyper::body::Bytes;
+/// # use http_body_util::Full;
+/// use cgi_rs::request::{BodyLengthPolicy, BodyOptions};
+/// use cgi_rs::{CGIError, CGIRequest};
+///
+/// let options = BodyOptions {
+///     max: 1024,
+///     policy: BodyLengthPolicy::Strict,
+///     require_complete: true,
+/// };
+/// let read = |body: &'static [u8]| {
+///     let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", "5")]);
+///     CGIRequest::<Full<Bytes>>::from_source_with_reader_and_options(env, body, options)
+/// };
+///
+/// assert_eq!(read(b"hello").unwrap().body_len(), 5);
+/// assert!(matches!(read(b"hello world"), Err(CGIError::ExcessRequestBody { declared: 5 })));
+/// assert!(matches!(read(b"hel"), Err(CGIError::ReadRequestBody { .. })));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodyOptions {
+    /// Refuse bodies larger than this many bytes with [`CGIError::ContentTooLarge`], checking `CONTENT_LENGTH` before
+    /// anything is read. Defaults to `usize::MAX`.
+    pub max: usize,
+    /// How a body longer than `CONTENT_LENGTH` is handled. Defaults to [`BodyLengthPolicy::Truncate`].
+    pub policy: BodyLengthPolicy,
+    /// Fail with [`CGIError::ReadRequestBody`] if the body is shorter than `CONTENT_LENGTH`, rather than keeping the
+    /// partial body and reporting it through [`CGIRequest::truncated_body`]. Defaults to `false`.
+    pub require_complete: bool,
+}
+
+impl Default for BodyOptions {
+    fn default() -> Self {
+        BodyOptions {
+            max: usize::MAX,
+            policy: BodyLengthPolicy::default(),
+            require_complete: false,
+        }
+    }
+}
+
+// The default limit on the length of the request URI.
+const DEFAULT_MAX_URI_LENGTH: usize = 8 * 1024;
+
+// The most that is read from stdin when the request doesn't declare a CONTENT_LENGTH.
+const UNSIZED_BODY_LIMIT: usize = 16 * 1024 * 1024;
+
+// The most buffer space reserved up front for a declared CONTENT_LENGTH.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+// How much of the request body to read from stdin.
+#[derive(Clone, Copy)]
+enum BodyLength {
+    // CONTENT_LENGTH was set.
+    Declared(usize),
+    // No CONTENT_LENGTH, so read to EOF, up to this limit.
+    Unsized(usize),
+    Empty,
+}
 
 pub struct CGIRequest<B>  {
-    pub request_body: B
+    pub request_body: B,
+    env: Box<dyn EnvSource>,
+    default_uri: Option<String>,
+    default_method: Option<Method>,
+    max_uri_length: usize,
+    absolute_uri: bool,
+    received_at: Instant,
+    truncated_body: Option<TruncatedBody>,
+    body_len: usize,
+    forwarded_headers: Vec<HeaderName>,
+    trusted_proxies: Vec<IpAddr>,
 }
 
 impl <B> CGIRequest<B> where B: Body {
+    fn new(request_body: B, env: Box<dyn EnvSource>) -> Self {
+        CGIRequest {
+            request_body,
+            env,
+            default_uri: None,
+            default_method: None,
+            max_uri_length: DEFAULT_MAX_URI_LENGTH,
+            absolute_uri: false,
+            received_at: Instant::now(),
+            truncated_body: None,
+            body_len: 0,
+            forwarded_headers: Vec::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    /// Creates a request from the environment, reading the body from stdin.
+    ///
+    /// If stdin closes before `CONTENT_LENGTH` bytes were read (e.g. the client disconnected mid-upload), the partial
+    /// body is kept and [`CGIRequest::truncated_body`] reports how much is missing. Use [`CGIRequest::from_env_strict`]
+    /// to fail instead.
     pub fn from_env() -> Result<CGIRequest<Full<Bytes>>> {
-        let content_length = MetaVariableKind::ContentLength
-            .from_env()
-            .map(|content_length| {
-                content_length
-                    .as_str()
-                    .and_then(|s| s.parse().context(error::InvalidContentLengthSnafu))
-            })
-            .transpose()?
-            .unwrap_or_default();
+        Self::from_env_with_limit(usize::MAX)
+    }
 
-        let read_content = Self::request_body_from_env(content_length)?;
+    /// Creates a request from the environment, failing with [`CGIError::ReadRequestBody`] if stdin provides fewer
+    /// than `CONTENT_LENGTH` bytes.
+    pub fn from_env_strict() -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_env_with_options(BodyOptions {
+            require_complete: true,
+            ..BodyOptions::default()
+        })
+    }
 
+    /// Creates a request from the environment, refusing bodies larger than `max` bytes.
+    ///
+    /// The declared `CONTENT_LENGTH` is checked before anything is allocated or read from stdin, so an oversized
+    /// request fails with [`CGIError::ContentTooLarge`] without consuming memory.
+    ///
+    /// If `CONTENT_LENGTH` is unset but the method can carry a body (`POST`, `PUT` or `PATCH`) and stdin isn't a
+    /// terminal, stdin is read to EOF instead, up to the smaller of `max` and 16MiB.
+    ///
+    /// Surrounding whitespace and a leading `+` in `CONTENT_LENGTH` are tolerated.
+    ///
+    /// ```rust
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::{CGIError, CGIRequest};
+    /// std::env::set_var("CONTENT_LENGTH", "999999999");
+    ///
+    /// let result = CGIRequest::<Full<Bytes>>::from_env_with_limit(1024);
+    /// assert!(matches!(
+    ///     result,
+    ///     Err(CGIError::ContentTooLarge { limit: 1024, declared: 999999999 })
+    /// ));
+    ///
+    /// for padded in [" 42", "+42", "42 \t"] {
+    ///     std::env::set_var("CONTENT_LENGTH", padded);
+    ///     let result = CGIRequest::<Full<Bytes>>::from_env_with_limit(1);
+    ///     assert!(matches!(result, Err(CGIError::ContentTooLarge { declared: 42, .. })));
+    /// }
+    ///
+    /// std::env::set_var("CONTENT_LENGTH", "4 2");
+    /// let result = CGIRequest::<Full<Bytes>>::from_env_with_limit(1);
+    /// assert!(matches!(result, Err(CGIError::InvalidContentLength { .. })));
+    /// ```
+    pub fn from_env_with_limit(max: usize) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_env_with_options(BodyOptions {
+            max,
+            ..BodyOptions::default()
+        })
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from stdin.
+    ///
+    /// The request keeps `source` and reads every meta-variable from it, never from the process environment.
+    pub fn from_source(source: impl EnvSource + 'static) -> Result<CGIRequest<Full<Bytes>>> {
+        let stdin = stdin();
+        let interactive = stdin.is_terminal();
+        Self::buffered(
+            Box::new(source),
+            stdin.lock(),
+            interactive,
+            BodyOptions::default(),
+            None,
+        )
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from `reader` instead of stdin.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::CGIRequest;
+    ///
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "POST"),
+    ///     ("REQUEST_URI", "/upload"),
+    ///     ("CONTENT_LENGTH", "11"),
+    /// ]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"hello world"[..]).unwrap();
+    /// assert_eq!(request.uri().unwrap(), "/upload");
+    /// assert!(request.truncated_body().is_none());
+    /// ```
+    pub fn from_source_with_reader(
+        source: impl EnvSource + 'static,
+        reader: impl Read,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_source_with_reader_and_policy(source, reader, BodyLengthPolicy::default())
+    }
+
+    /// Creates a request from the environment, reconciling the body read from stdin with `CONTENT_LENGTH` according
+    /// to `policy`.
+    pub fn from_env_with_policy(policy: BodyLengthPolicy) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_env_with_options(BodyOptions {
+            policy,
+            ..BodyOptions::default()
+        })
+    }
+
+    /// Creates a request from the environment, reading the body from stdin as `options` describe.
+    pub fn from_env_with_options(options: BodyOptions) -> Result<CGIRequest<Full<Bytes>>> {
+        let stdin = stdin();
+        let interactive = stdin.is_terminal();
+        Self::buffered(
+            Box::new(ProcessEnv),
+            stdin.lock(),
+            interactive,
+            options,
+            None,
+        )
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from `reader` and reconciling it with
+    /// `CONTENT_LENGTH` according to `policy`.
+    pub fn from_source_with_reader_and_policy(
+        source: impl EnvSource + 'static,
+        reader: impl Read,
+        policy: BodyLengthPolicy,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        let options = BodyOptions {
+            policy,
+            ..BodyOptions::default()
+        };
+        Self::from_source_with_reader_and_options(source, reader, options)
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from `reader` as `options` describe.
+    /// See [`BodyOptions`].
+    pub fn from_source_with_reader_and_options(
+        source: impl EnvSource + 'static,
+        reader: impl Read,
+        options: BodyOptions,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::buffered(Box::new(source), reader, false, options, None)
+    }
+
+    /// Creates a request from the environment like [`CGIRequest::from_env`], reading the body into a buffer taken
+    /// from `pool`.
+    pub fn from_env_with_pool(pool: &BufferPool) -> Result<CGIRequest<Full<Bytes>>> {
+        let stdin = stdin();
+        let interactive = stdin.is_terminal();
+        Self::buffered(
+            Box::new(ProcessEnv),
+            stdin.lock(),
+            interactive,
+            BodyOptions::default(),
+            Some(pool),
+        )
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from `reader` into a buffer taken
+    /// from `pool`.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use cgi_rs::{body::BufferPool, CGIRequest};
+    /// use http_body_util::{BodyExt, Full};
+    /// use hyper::body::Bytes;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let pool = BufferPool::new(1);
+    /// for body in ["a longer first body", "short"] {
+    ///     let env = HashMap::from([
+    ///         ("REQUEST_METHOD", "POST".to_string()),
+    ///         ("CONTENT_LENGTH", body.len().to_string()),
+    ///     ]);
+    ///     let request = CGIRequest::<Full<Bytes>>::from_source_with_reader_and_pool(env, body.as_bytes(), &pool);
+    ///     let bytes = request.unwrap().request_body.collect().await.unwrap().to_bytes();
+    ///     assert_eq!(bytes, body);
+    ///     pool.recycle(bytes);
+    ///     assert_eq!(pool.len(), 1);
+    /// }
+    /// # }
+    /// ```
+    pub fn from_source_with_reader_and_pool(
+        source: impl EnvSource + 'static,
+        reader: impl Read,
+        pool: &BufferPool,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::buffered(
+            Box::new(source),
+            reader,
+            false,
+            BodyOptions::default(),
+            Some(pool),
+        )
+    }
+
+    fn buffered(
+        env: Box<dyn EnvSource>,
+        reader: impl Read,
+        interactive: bool,
+        options: BodyOptions,
+        pool: Option<&BufferPool>,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        let body_length = Self::body_length(&*env, interactive, options.max)?;
+        let mut read_content = Self::body_buffer(body_length, pool);
+        reader
+            .take(Self::read_limit(body_length, options, interactive))
+            .read_to_end(&mut read_content)
+            .context(error::ReadRequestBodySnafu)?;
+
+        Self::check_body(&read_content, body_length, options)?;
+        Ok(Self::from_content(env, read_content, body_length))
+    }
+
+    /// Creates a request from the environment like [`CGIRequest::from_env`], reading the body from stdin without
+    /// blocking the async runtime.
+    ///
+    /// ```rust
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// std::env::set_var("REQUEST_METHOD", "GET");
+    /// std::env::set_var("REQUEST_URI", "/");
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_env_async().await.unwrap();
+    /// assert_eq!(request.uri().unwrap(), "/");
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn from_env_async() -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_env_async_with_options(BodyOptions::default()).await
+    }
+
+    /// Creates a request from the environment like [`CGIRequest::from_env_with_options`], reading the body from stdin
+    /// without blocking the async runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn from_env_async_with_options(
+        options: BodyOptions,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        let interactive = stdin().is_terminal();
+        Self::buffered_async(
+            Box::new(ProcessEnv),
+            AsyncStdin::new(),
+            interactive,
+            options,
+            None,
+        )
+        .await
+    }
+
+    /// Creates a request from the environment like [`CGIRequest::from_env_with_pool`], reading the body from stdin
+    /// without blocking the async runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn from_env_async_with_pool(pool: &BufferPool) -> Result<CGIRequest<Full<Bytes>>> {
+        let interactive = stdin().is_terminal();
+        let stdin = AsyncStdin::new();
+        Self::buffered_async(
+            Box::new(ProcessEnv),
+            stdin,
+            interactive,
+            BodyOptions::default(),
+            Some(pool),
+        )
+        .await
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from the async `reader`.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::{BodyExt, Full};
+    /// # use cgi_rs::CGIRequest;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", "5")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_async_reader(env, &b"hello world"[..]).await.unwrap();
+    /// assert_eq!(request.request_body.collect().await.unwrap().to_bytes(), "hello");
+    ///
+    /// // A declared length the body doesn't have isn't allocated up front.
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST".to_string()), ("CONTENT_LENGTH", usize::MAX.to_string())]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_async_reader(env, &b"hello"[..]).await.unwrap();
+    /// assert_eq!(request.body_len(), 5);
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn from_source_with_async_reader(
+        source: impl EnvSource + 'static,
+        reader: impl tokio::io::AsyncRead + Unpin,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::from_source_with_async_reader_and_options(source, reader, BodyOptions::default())
+            .await
+    }
+
+    /// Creates a request from the meta-variables in `source`, reading the body from the async `reader` as `options`
+    /// describe.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::request::{BodyLengthPolicy, BodyOptions};
+    /// use cgi_rs::{CGIError, CGIRequest};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let read = |content_length: &'static str, options| {
+    ///     let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", content_length)]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_async_reader_and_options(env, &b"hello world"[..], options)
+    /// };
+    ///
+    /// let limited = BodyOptions { max: 8, ..BodyOptions::default() };
+    /// assert!(matches!(read("11", limited).await, Err(CGIError::ContentTooLarge { limit: 8, declared: 11 })));
+    ///
+    /// let strict = BodyOptions { policy: BodyLengthPolicy::Strict, ..BodyOptions::default() };
+    /// assert!(matches!(read("5", strict).await, Err(CGIError::ExcessRequestBody { declared: 5 })));
+    ///
+    /// let complete = BodyOptions { require_complete: true, ..BodyOptions::default() };
+    /// assert!(matches!(read("20", complete).await, Err(CGIError::ReadRequestBody { .. })));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn from_source_with_async_reader_and_options(
+        source: impl EnvSource + 'static,
+        reader: impl tokio::io::AsyncRead + Unpin,
+        options: BodyOptions,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        Self::buffered_async(Box::new(source), reader, false, options, None).await
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn buffered_async(
+        env: Box<dyn EnvSource>,
+        reader: impl tokio::io::AsyncRead + Unpin,
+        interactive: bool,
+        options: BodyOptions,
+        pool: Option<&BufferPool>,
+    ) -> Result<CGIRequest<Full<Bytes>>> {
+        use tokio::io::AsyncReadExt;
+
+        let body_length = Self::body_length(&*env, interactive, options.max)?;
+        let mut read_content = Self::body_buffer(body_length, pool);
+        reader
+            .take(Self::read_limit(body_length, options, interactive))
+            .read_to_end(&mut read_content)
+            .await
+            .context(error::ReadRequestBodySnafu)?;
+
+        Self::check_body(&read_content, body_length, options)?;
+        Ok(Self::from_content(env, read_content, body_length))
+    }
+
+    // A buffer for the body, taken from `pool` if there is one. `CONTENT_LENGTH` can't be trusted to match the body,
+    // so only part of it is reserved up front, and the buffer grows as the body is actually read.
+    fn body_buffer(body_length: BodyLength, pool: Option<&BufferPool>) -> Vec<u8> {
+        let mut buffer = pool.map(BufferPool::take).unwrap_or_default();
+        if let BodyLength::Declared(content_length) = body_length {
+            buffer.reserve(content_length.min(MAX_PREALLOCATION));
+        }
+        buffer
+    }
+
+    // The most that's read to EOF, if the body is read to EOF rather than to `CONTENT_LENGTH`.
+    fn eof_limit(body_length: BodyLength, options: BodyOptions) -> Option<usize> {
+        match body_length {
+            BodyLength::Declared(_) if options.policy == BodyLengthPolicy::ReadAll => {
+                Some(options.max.min(UNSIZED_BODY_LIMIT))
+            }
+            BodyLength::Unsized(limit) => Some(limit),
+            BodyLength::Declared(_) | BodyLength::Empty => None,
+        }
+    }
+
+    // How many bytes to read. Where a longer body is an error, this is one more than is accepted so that it's noticed,
+    // which under `BodyLengthPolicy::Strict` means waiting for EOF, unless stdin is a terminal.
+    fn read_limit(body_length: BodyLength, options: BodyOptions, interactive: bool) -> u64 {
+        if let Some(limit) = Self::eof_limit(body_length, options) {
+            return (limit as u64).saturating_add(1);
+        }
+        match body_length {
+            BodyLength::Declared(content_length)
+                if options.policy == BodyLengthPolicy::Strict && !interactive =>
+            {
+                (content_length as u64).saturating_add(1)
+            }
+            BodyLength::Declared(content_length) => content_length as u64,
+            BodyLength::Unsized(_) | BodyLength::Empty => 0,
+        }
+    }
+
+    // Checks a body read up to `read_limit` against the limits in `options`.
+    fn check_body(
+        read_content: &[u8],
+        body_length: BodyLength,
+        options: BodyOptions,
+    ) -> Result<()> {
+        if let Some(limit) = Self::eof_limit(body_length, options) {
+            ensure!(
+                read_content.len() <= limit,
+                error::ContentTooLargeSnafu {
+                    limit,
+                    declared: read_content.len(),
+                }
+            );
+        } else if let BodyLength::Declared(content_length) = body_length {
+            ensure!(
+                read_content.len() <= content_length,
+                error::ExcessRequestBodySnafu {
+                    declared: content_length
+                }
+            );
+        }
+
+        if let BodyLength::Declared(content_length) = body_length {
+            if options.require_complete && read_content.len() < content_length {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+                    .context(error::ReadRequestBodySnafu);
+            }
+        }
+        Ok(())
+    }
+
+    fn body_length(env: &dyn EnvSource, interactive: bool, max: usize) -> Result<BodyLength> {
+        Ok(match Self::content_length(env)? {
+            Some(content_length) => {
+                ensure!(
+                    content_length <= max,
+                    error::ContentTooLargeSnafu {
+                        limit: max,
+                        declared: content_length,
+                    }
+                );
+                BodyLength::Declared(content_length)
+            }
+            None if !interactive && Self::expects_unsized_body(env) => {
+                BodyLength::Unsized(max.min(UNSIZED_BODY_LIMIT))
+            }
+            None => BodyLength::Empty,
+        })
+    }
+
+    fn from_content(
+        env: Box<dyn EnvSource>,
+        read_content: Vec<u8>,
+        body_length: BodyLength,
+    ) -> CGIRequest<Full<Bytes>> {
+        let truncated_body = match body_length {
+            BodyLength::Declared(declared) if read_content.len() < declared => {
+                Some(TruncatedBody {
+                    declared,
+                    received: read_content.len(),
+                })
+            }
+            _ => None,
+        };
+
+        let body_len = read_content.len();
         let request_body = Bytes::from(read_content);
 
         let full = Full::from(request_body);
 
-        let result = CGIRequest { request_body: full };
+        let mut result = CGIRequest::new(full, env);
+        result.truncated_body = truncated_body;
+        result.body_len = body_len;
+
+        result
+    }
+
+    /// Creates a request whose body is read lazily from stdin as it is polled.
+    ///
+    /// Unlike [`CGIRequest::from_env`], the body is never fully buffered in memory, so this is suited to scripts
+    /// accepting large uploads. At most `CONTENT_LENGTH` bytes are read.
+    pub fn from_env_streaming() -> Result<CGIRequest<ReaderBody<Stdin>>> {
+        let content_length = Self::content_length(&ProcessEnv)?.unwrap_or_default();
+
+        Ok(CGIRequest::new(
+            ReaderBody::new(stdin(), content_length),
+            Box::new(ProcessEnv),
+        ))
+    }
 
-        Ok(result)
+    fn content_length(env: &dyn EnvSource) -> Result<Option<usize>> {
+        MetaVariableKind::ContentLength
+            .from_source(env)
+            .map(|content_length| content_length.parse_content_length())
+            .transpose()
+    }
+
+    // Servers forwarding chunked requests may omit CONTENT_LENGTH entirely, in which case the body can only be found by
+    // reading stdin to EOF.
+    fn expects_unsized_body(env: &dyn EnvSource) -> bool {
+        MetaVariableKind::RequestMethod
+            .from_source(env)
+            .is_some_and(|method| matches!(method.as_bytes(), b"POST" | b"PUT" | b"PATCH"))
+    }
+
+    /// Use `uri` as the request URI when neither `REQUEST_URI` nor `SCRIPT_NAME` is set.
+    ///
+    /// By default, [`CGIRequest::uri`] returns [`CGIError::CannotDetermineUri`] in that case.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([("REQUEST_METHOD", "GET")]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert!(matches!(request.uri(), Err(cgi_rs::CGIError::CannotDetermineUri)));
+    ///
+    /// let request = request.with_default_uri("/");
+    /// assert_eq!(request.uri().unwrap(), "/");
+    /// ```
+    pub fn with_default_uri(mut self, uri: impl Into<String>) -> Self {
+        self.default_uri = Some(uri.into());
+        self
+    }
+
+    /// Use `method` as the request method when `REQUEST_METHOD` is unset or empty, as some broken gateways send.
+    ///
+    /// By default, converting the request fails with [`CGIError::MetaVariableNotSet`] or [`CGIError::EmptyMethod`]
+    /// in those cases.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// use hyper::{Method, Request};
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::{CGIError, CGIRequest};
+    /// let request = || {
+    ///     let env = HashMap::from([("REQUEST_METHOD", ""), ("REQUEST_URI", "/")]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap()
+    /// };
+    ///
+    /// assert!(matches!(Request::try_from(request()), Err(CGIError::EmptyMethod)));
+    ///
+    /// let converted = Request::try_from(request().with_default_method(Method::GET)).unwrap();
+    /// assert_eq!(converted.method(), Method::GET);
+    /// ```
+    pub fn with_default_method(mut self, method: Method) -> Self {
+        self.default_method = Some(method);
+        self
+    }
+
+    /// Limits the length of the request URI to `max` bytes. Defaults to 8KiB.
+    ///
+    /// [`CGIRequest::uri`] returns [`CGIError::UriTooLong`] for longer URIs, which should be answered with
+    /// `414 URI Too Long`.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::{CGIError, CGIRequest};
+    /// let uri = format!("/{}", "a".repeat(8 * 1024));
+    /// let env = HashMap::from([("REQUEST_URI", uri)]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert!(matches!(request.uri(), Err(CGIError::UriTooLong { length: 8193, limit: 8192 })));
+    ///
+    /// let request = request.with_max_uri_length(16 * 1024);
+    /// assert!(request.uri().is_ok());
+    /// ```
+    pub fn with_max_uri_length(mut self, max: usize) -> Self {
+        self.max_uri_length = max;
+        self
+    }
+
+    /// Build an absolute-form request URI (`scheme://host/path?query`) from `HTTP_HOST`, so that
+    /// [`Uri::authority`](hyper::Uri::authority) is populated for host-based routing.
+    ///
+    /// The scheme is `https` if [`CGIRequest::is_secure`], `http` otherwise. Any port in `HTTP_HOST` is kept. When
+    /// `HTTP_HOST` is unset the URI stays in origin-form, and a host containing anything other than visible ASCII
+    /// authority characters is rejected with [`CGIError::InvalidHost`]. Behind a reverse proxy, see
+    /// [`CGIRequest::with_trusted_proxies`].
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// use hyper::Request;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "GET"),
+    ///     ("REQUEST_URI", "/index?page=2"),
+    ///     ("HTTP_HOST", "example.com:8080"),
+    /// ]);
+    ///
+    /// let request: Request<Full<Bytes>> = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+    ///     .map(CGIRequest::with_absolute_uri)
+    ///     .and_then(Request::try_from)
+    ///     .unwrap();
+    /// assert_eq!(request.uri().host(), Some("example.com"));
+    /// assert_eq!(request.uri().port_u16(), Some(8080));
+    /// assert_eq!(request.uri().to_string(), "http://example.com:8080/index?page=2");
+    /// ```
+    pub fn with_absolute_uri(mut self) -> Self {
+        self.absolute_uri = true;
+        self
+    }
+
+    /// Forward `headers` to the converted [`Request`], in addition to the built-in set.
+    ///
+    /// Each header is read from its `HTTP_*` meta-variable, e.g. `X-Request-Id` from `HTTP_X_REQUEST_ID`, and skipped
+    /// if that isn't set.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// use hyper::{header::HeaderName, Request};
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "GET"),
+    ///     ("REQUEST_URI", "/"),
+    ///     ("HTTP_X_REQUEST_ID", "f058ebd6"),
+    /// ]);
+    ///
+    /// let request: Request<Full<Bytes>> = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+    ///     .map(|request| {
+    ///         request.with_forwarded_headers([
+    ///             HeaderName::from_static("x-request-id"),
+    ///             HeaderName::from_static("x-forwarded-for"),
+    ///         ])
+    ///     })
+    ///     .and_then(Request::try_from)
+    ///     .unwrap();
+    /// assert_eq!(request.headers()["X-Request-Id"], "f058ebd6");
+    /// assert!(!request.headers().contains_key("X-Forwarded-For"));
+    /// ```
+    pub fn with_forwarded_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.forwarded_headers.extend(headers);
+        self
+    }
+
+    /// Trust the `X-Forwarded-Host` header of requests whose `REMOTE_ADDR` is one of `proxies`, using it in place of
+    /// `HTTP_HOST` for [`CGIRequest::with_absolute_uri`] and [`CGIRequest::require_https`].
+    ///
+    /// Reverse proxies often rewrite `Host` to the backend's name, which makes absolute URLs point at the backend. If
+    /// the header lists several hosts, the first (the one the client asked for) is used. Requests from any other
+    /// address keep using `HTTP_HOST`, since the header is trivially forged.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use std::net::{IpAddr, Ipv4Addr};
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let proxy = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    /// let uri = |remote_addr: &'static str| {
+    ///     let env = HashMap::from([
+    ///         ("REQUEST_URI", "/login"),
+    ///         ("REMOTE_ADDR", remote_addr),
+    ///         ("HTTP_HOST", "backend.internal"),
+    ///         ("HTTP_X_FORWARDED_HOST", "example.com, edge.example.net"),
+    ///     ]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+    ///         .unwrap()
+    ///         .with_absolute_uri()
+    ///         .with_trusted_proxies([proxy])
+    ///         .uri()
+    ///         .unwrap()
+    /// };
+    ///
+    /// assert_eq!(uri("10.0.0.1"), "http://example.com/login");
+    /// assert_eq!(uri("203.0.113.7"), "http://backend.internal/login");
+    /// ```
+    pub fn with_trusted_proxies(mut self, proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.trusted_proxies.extend(proxies);
+        self
+    }
+
+    /// Returns when this request was constructed from the environment.
+    ///
+    /// CGI scripts are started per request, so this is a reasonable proxy for when the request was received.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([("REQUEST_METHOD", "GET")]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_millis(5));
+    /// assert!(!request.received_at().elapsed().is_zero());
+    /// ```
+    pub fn received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    /// Returns how much of the body was missing if stdin closed before `CONTENT_LENGTH` bytes were read.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::TruncatedBody, CGIRequest};
+    ///
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", "10")]);
+    ///
+    /// // The client disconnected after sending half of the body.
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"hello"[..]).unwrap();
+    /// assert_eq!(
+    ///     request.truncated_body(),
+    ///     Some(TruncatedBody { declared: 10, received: 5 })
+    /// );
+    ///
+    /// // Nothing is allocated up front for a length the body doesn't have.
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST".to_string()), ("CONTENT_LENGTH", usize::MAX.to_string())]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"hello"[..]).unwrap();
+    /// assert_eq!(request.body_len(), 5);
+    /// ```
+    pub fn truncated_body(&self) -> Option<TruncatedBody> {
+        self.truncated_body
+    }
+
+    /// Returns the number of body bytes read from stdin, e.g. for logging request sizes.
+    ///
+    /// This is the actual length read, which may differ from `CONTENT_LENGTH` if the body was truncated or read to
+    /// EOF. Streaming requests read nothing up front, so this is always 0 for them.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST"), ("CONTENT_LENGTH", "5")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"hello world"[..]).unwrap();
+    /// assert_eq!(request.body_len(), 5);
+    ///
+    /// // Without CONTENT_LENGTH, the body is read to EOF.
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &b"hello world"[..]).unwrap();
+    /// assert_eq!(request.body_len(), 11);
+    /// ```
+    pub fn body_len(&self) -> usize {
+        self.body_len
     }
 
     pub fn var(&self, kind: MetaVariableKind) -> Option<MetaVariable> {
-        kind.from_env()
+        kind.from_source(&*self.env)
+    }
+
+    /// Returns the raw bytes of the header `http_name` (e.g. `X-Signature`), read from its `HTTP_*` meta-variable
+    /// without any encoding validation.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::ffi::OsStr;
+    /// use std::os::unix::ffi::OsStrExt;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    ///
+    /// let signature = OsStr::from_bytes(b"\xff\x00sig").to_os_string();
+    /// let env = HashMap::from([("HTTP_X_SIGNATURE", signature)]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert_eq!(request.raw_header("X-Signature").unwrap(), b"\xff\x00sig");
+    /// assert!(request.raw_header("X-Missing").is_none());
+    /// ```
+    pub fn raw_header(&self, http_name: &str) -> Option<Vec<u8>> {
+        let variable = format!("HTTP_{}", http_name.to_ascii_uppercase().replace('-', "_"));
+        self.env
+            .get(&variable)
+            .map(|value| value.as_encoded_bytes().to_vec())
     }
 
     fn try_var(&self, kind: MetaVariableKind) -> Result<MetaVariable> {
-        kind.try_from_env()
+        kind.try_from_source(&*self.env)
+    }
+
+    fn auth(&self) -> Option<CgiAuth> {
+        let string_var = |kind| Some(self.var(kind)?.as_str().ok()?.to_string());
+        let auth = CgiAuth {
+            auth_type: string_var(MetaVariableKind::AuthType),
+            remote_user: string_var(MetaVariableKind::RemoteUser),
+        };
+
+        (auth.auth_type.is_some() || auth.remote_user.is_some()).then_some(auth)
+    }
+
+    // The host the client asked for: the first `X-Forwarded-Host` entry if the request came from a trusted proxy,
+    // `HTTP_HOST` otherwise.
+    fn host(&self) -> Option<MetaVariable> {
+        let from_trusted_proxy = self
+            .remote_ip()
+            .is_some_and(|ip| self.trusted_proxies.contains(&ip));
+        let forwarded_host = from_trusted_proxy
+            .then(|| self.var(MetaVariableKind::HttpXForwardedHost))
+            .flatten();
+
+        match forwarded_host {
+            Some(MetaVariable { kind, value }) => {
+                let value = match value.to_str() {
+                    Some(hosts) => hosts.split(',').next().unwrap_or_default().trim().into(),
+                    None => value,
+                };
+                Some(MetaVariable { kind, value })
+            }
+            None => self.var(MetaVariableKind::HttpHost),
+        }
+    }
+
+    fn remote_ip(&self) -> Option<IpAddr> {
+        self.var(MetaVariableKind::RemoteAddr)?.parse_ip_addr().ok()
+    }
+
+    fn remote_port(&self) -> Option<u16> {
+        self.var(MetaVariableKind::RemotePort)?.parse_port().ok()
+    }
+
+    /// Returns true if the request was received over HTTPS.
+    ///
+    /// The `HTTPS` variable set by Apache, nginx and lighttpd is checked first (`on` or `1` means HTTPS), falling
+    /// back to `REQUEST_SCHEME` if it is unset.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{CGIRequest, MetaVariableKind};
+    ///
+    /// let env = HashMap::from([("HTTPS", "on")]);
+    /// assert_eq!(MetaVariableKind::Https.to_string(), "HTTPS");
+    /// assert_eq!(MetaVariableKind::Https.from_source(&env).unwrap().as_str().unwrap(), "on");
+    ///
+    /// let is_secure = |env: HashMap<&'static str, &'static str>| {
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().is_secure()
+    /// };
+    /// assert!(is_secure(HashMap::from([("HTTPS", "ON")])));
+    /// assert!(is_secure(HashMap::from([("REQUEST_SCHEME", "https")])));
+    /// assert!(!is_secure(HashMap::from([("HTTPS", "off"), ("REQUEST_SCHEME", "https")])));
+    /// assert!(!is_secure(HashMap::new()));
+    /// ```
+    pub fn is_secure(&self) -> bool {
+        if let Some(https) = self.var(MetaVariableKind::Https) {
+            return https.as_bytes().eq_ignore_ascii_case(b"on") || https.as_bytes() == b"1";
+        }
+        self.var(MetaVariableKind::RequestScheme)
+            .is_some_and(|scheme| scheme.as_bytes().eq_ignore_ascii_case(b"https"))
+    }
+
+    /// Returns a response redirecting the client to the HTTPS version of this request, or `None` if the request is
+    /// already secure.
+    ///
+    /// The redirect target is built from `HTTP_HOST` (falling back to `SERVER_NAME`, see also
+    /// [`CGIRequest::with_trusted_proxies`]) and the request URI. If no host
+    /// is available, a `403 Forbidden` response is returned instead, since there's nowhere to redirect to.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let env = HashMap::from([
+    ///     ("REQUEST_SCHEME", "http"),
+    ///     ("HTTP_HOST", "example.com"),
+    ///     ("REQUEST_URI", "/login?next=%2F"),
+    /// ]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// let redirect = request.require_https().unwrap();
+    /// assert_eq!(redirect.status, "301");
+    /// assert_eq!(redirect.headers["Location"], "https://example.com/login?next=%2F");
+    ///
+    /// let env = HashMap::from([("REQUEST_SCHEME", "https"), ("REQUEST_URI", "/login")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert!(request.require_https().is_none());
+    /// ```
+    pub fn require_https(&self) -> Option<CGIResponse> {
+        if self.is_secure() {
+            return None;
+        }
+
+        let host = self
+            .host()
+            .or_else(|| self.var(MetaVariableKind::ServerName));
+        let location = host.and_then(|host| {
+            let path = self.origin_form_uri().unwrap_or_else(|_| String::from("/"));
+            HeaderValue::try_from(format!("https://{}{}", host.as_str().ok()?, path)).ok()
+        });
+
+        Some(match location {
+            Some(location) => {
+                let mut response = CGIResponse::new(StatusCode::MOVED_PERMANENTLY);
+                response.headers.insert(LOCATION, location);
+                response
+            }
+            None => CGIResponse::new(StatusCode::FORBIDDEN),
+        })
+    }
+
+    /// Returns true if the client sent the `Save-Data: on` client hint, asking for a reduced data usage.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let save_data = |value: Option<&'static str>| {
+    ///     let env: HashMap<_, _> = value.map(|value| ("HTTP_SAVE_DATA", value)).into_iter().collect();
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().save_data()
+    /// };
+    ///
+    /// assert!(!save_data(None));
+    /// assert!(save_data(Some("on")));
+    /// assert!(!save_data(Some("off")));
+    /// ```
+    pub fn save_data(&self) -> bool {
+        self.var(MetaVariableKind::HttpSaveData)
+            .is_some_and(|value| value.as_bytes().trim_ascii().eq_ignore_ascii_case(b"on"))
+    }
+
+    /// Returns the request's Fetch Metadata headers.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::FetchMetadata, CGIRequest};
+    ///
+    /// let env = HashMap::from([
+    ///     ("HTTP_SEC_FETCH_SITE", "same-origin"),
+    ///     ("HTTP_SEC_FETCH_MODE", "navigate"),
+    ///     ("HTTP_SEC_FETCH_DEST", "document"),
+    ///     ("HTTP_SEC_FETCH_USER", "?1"),
+    /// ]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert_eq!(
+    ///     request.fetch_metadata(),
+    ///     FetchMetadata {
+    ///         site: Some("same-origin".to_string()),
+    ///         mode: Some("navigate".to_string()),
+    ///         dest: Some("document".to_string()),
+    ///         user: Some(true),
+    ///     }
+    /// );
+    ///
+    /// let env = HashMap::from([("HTTP_SEC_FETCH_MODE", "no-cors")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// let metadata = request.fetch_metadata();
+    /// assert_eq!(metadata.mode.as_deref(), Some("no-cors"));
+    /// assert_eq!((metadata.site, metadata.dest, metadata.user), (None, None, None));
+    /// ```
+    pub fn fetch_metadata(&self) -> FetchMetadata {
+        let string = |kind| {
+            self.var(kind)
+                .and_then(|value| value.as_str().ok().map(|value| value.trim().to_string()))
+        };
+        FetchMetadata {
+            site: string(MetaVariableKind::HttpSecFetchSite),
+            mode: string(MetaVariableKind::HttpSecFetchMode),
+            dest: string(MetaVariableKind::HttpSecFetchDest),
+            user: string(MetaVariableKind::HttpSecFetchUser)
+                .and_then(|user| parse_sf_boolean(&user)),
+        }
+    }
+
+    /// Returns the request's Client Hints headers.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::ClientHints, CGIRequest};
+    ///
+    /// let env = HashMap::from([
+    ///     ("HTTP_SEC_CH_UA_MOBILE", "?1"),
+    ///     ("HTTP_SEC_CH_UA_PLATFORM", "\"Android\""),
+    ///     ("HTTP_VIEWPORT_WIDTH", "412"),
+    ///     ("HTTP_DPR", "2.625"),
+    /// ]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert_eq!(
+    ///     request.client_hints(),
+    ///     ClientHints {
+    ///         ua: None,
+    ///         mobile: Some(true),
+    ///         platform: Some("Android".to_string()),
+    ///         viewport_width: Some(412),
+    ///         dpr: Some(2.625),
+    ///     }
+    /// );
+    ///
+    /// let env = HashMap::from([("HTTP_SEC_CH_UA", "\"Chromium\";v=\"124\""), ("HTTP_DPR", "high")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// let hints = request.client_hints();
+    /// assert_eq!(hints.ua.as_deref(), Some("\"Chromium\";v=\"124\""));
+    /// assert_eq!(hints.dpr, None);
+    /// ```
+    pub fn client_hints(&self) -> ClientHints {
+        let string = |kind| {
+            self.var(kind)
+                .and_then(|value| value.as_str().ok().map(|value| value.trim().to_string()))
+        };
+        ClientHints {
+            ua: string(MetaVariableKind::HttpSecChUa),
+            mobile: string(MetaVariableKind::HttpSecChUaMobile)
+                .and_then(|mobile| parse_sf_boolean(&mobile)),
+            platform: string(MetaVariableKind::HttpSecChUaPlatform).map(|platform| {
+                platform
+                    .strip_prefix('"')
+                    .and_then(|platform| platform.strip_suffix('"'))
+                    .map_or(platform.clone(), str::to_string)
+            }),
+            viewport_width: string(MetaVariableKind::HttpViewportWidth)
+                .and_then(|width| width.parse().ok()),
+            dpr: string(MetaVariableKind::HttpDpr)
+                .and_then(|dpr| dpr.parse::<f32>().ok())
+                .filter(|dpr| dpr.is_finite() && *dpr > 0.0),
+        }
+    }
+
+    /// Returns the request's W3C Trace Context, or `None` if `traceparent` is unset or malformed, in which case the
+    /// request should start a new trace.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::TraceContext, CGIRequest};
+    ///
+    /// let trace_context = |traceparent: &'static str| {
+    ///     let env = HashMap::from([("HTTP_TRACEPARENT", traceparent), ("HTTP_TRACESTATE", "congo=t61rcWkgMzE")]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().trace_context()
+    /// };
+    ///
+    /// let context = trace_context("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01").unwrap();
+    /// assert_eq!(
+    ///     context,
+    ///     TraceContext {
+    ///         version: 0,
+    ///         trace_id: 0x0af7651916cd43dd8448eb211c80319c,
+    ///         parent_id: 0xb7ad6b7169203331,
+    ///         flags: 1,
+    ///         tracestate: Some("congo=t61rcWkgMzE".to_string()),
+    ///     }
+    /// );
+    /// assert!(context.is_sampled());
+    ///
+    /// for malformed in [
+    ///     "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+    ///     "00-0AF7651916CD43DD8448EB211C80319C-B7AD6B7169203331-01",
+    ///     "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+    ///     "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+    ///     "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+    ///     "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+    ///     "0-af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+    /// ] {
+    ///     assert_eq!(trace_context(malformed), None, "{}", malformed);
+    /// }
+    /// ```
+    pub fn trace_context(&self) -> Option<TraceContext> {
+        let traceparent = self.var(MetaVariableKind::HttpTraceparent)?;
+        let (version, trace_id, parent_id, flags) = parse_traceparent(traceparent.as_str().ok()?)?;
+        let tracestate = self
+            .var(MetaVariableKind::HttpTracestate)
+            .and_then(|tracestate| {
+                tracestate
+                    .as_str()
+                    .ok()
+                    .map(|tracestate| tracestate.trim().to_string())
+            })
+            .filter(|tracestate| !tracestate.is_empty());
+
+        Some(TraceContext {
+            version,
+            trace_id,
+            parent_id,
+            flags,
+            tracestate,
+        })
+    }
+
+    /// Returns the proxies listed in `HTTP_VIA`, in the order the request passed through them, or `None` if the header
+    /// is unset or isn't valid UTF-8.
+    ///
+    /// Each entry is a hop such as `1.1 proxy.example.com (squid/5.7)`: the received protocol, the proxy's host or
+    /// pseudonym, and an optional comment. Commas inside comments don't separate hops.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::CGIRequest;
+    ///
+    /// let via = |via: &'static str| {
+    ///     let env = HashMap::from([("HTTP_VIA", via)]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().via()
+    /// };
+    ///
+    /// assert_eq!(via("1.1 proxy.example.com").unwrap(), ["1.1 proxy.example.com"]);
+    /// assert_eq!(
+    ///     via("1.0 fred, 1.1 p.example.net (Apache/2.4, mod_proxy) ,HTTP/2 edge").unwrap(),
+    ///     ["1.0 fred", "1.1 p.example.net (Apache/2.4, mod_proxy)", "HTTP/2 edge"]
+    /// );
+    /// ```
+    pub fn via(&self) -> Option<Vec<String>> {
+        let via = self.var(MetaVariableKind::HttpVia)?;
+        let via = via.as_str().ok()?;
+
+        let mut hops = Vec::new();
+        let mut depth = 0usize;
+        let mut start = 0;
+        for (i, c) in via.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    hops.push(&via[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        hops.push(&via[start..]);
+
+        Some(
+            hops.into_iter()
+                .map(str::trim)
+                .filter(|hop| !hop.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+
+    /// Returns whether the request was made from the same site, according to `Sec-Fetch-Site`, as a simple defense
+    /// against cross-site request forgery.
+    ///
+    /// `same-origin`, `same-site` and `none` (a user-initiated request, e.g. a bookmark) count as the same site, and
+    /// `cross-site` doesn't. Returns `None` if the header is absent, as it is from older clients, or unrecognized.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{CGIRequest, CGIResponse};
+    ///
+    /// let is_same_site = |site: Option<&'static str>| {
+    ///     let env: HashMap<_, _> = site.map(|site| ("HTTP_SEC_FETCH_SITE", site)).into_iter().collect();
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().is_same_site()
+    /// };
+    ///
+    /// assert_eq!(is_same_site(Some("same-origin")), Some(true));
+    /// assert_eq!(is_same_site(Some("cross-site")), Some(false));
+    /// assert_eq!(is_same_site(None), None);
+    ///
+    /// if is_same_site(Some("cross-site")) == Some(false) {
+    ///     let response = CGIResponse::forbidden_cross_site();
+    ///     assert_eq!(response.status, "403");
+    /// }
+    /// ```
+    pub fn is_same_site(&self) -> Option<bool> {
+        match self.fetch_metadata().site?.to_ascii_lowercase().as_str() {
+            "same-origin" | "same-site" | "none" => Some(true),
+            "cross-site" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Parses the request's `Cache-Control` directives. If the header is absent, no directives are set.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::CacheControlDirectives, CGIRequest};
+    ///
+    /// let cache_control = |value: &'static str| {
+    ///     let env = HashMap::from([("HTTP_CACHE_CONTROL", value)]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().cache_control()
+    /// };
+    ///
+    /// assert!(cache_control("no-cache").no_cache);
+    /// assert_eq!(cache_control("max-age=0").max_age, Some(0));
+    ///
+    /// let directives = cache_control("No-Store, max-age=\"60\", max-stale, min-fresh=10");
+    /// assert_eq!(
+    ///     directives,
+    ///     CacheControlDirectives {
+    ///         no_store: true,
+    ///         max_age: Some(60),
+    ///         max_stale: Some(None),
+    ///         min_fresh: Some(10),
+    ///         ..Default::default()
+    ///     }
+    /// );
+    /// ```
+    pub fn cache_control(&self) -> CacheControlDirectives {
+        self.var(MetaVariableKind::HttpCacheControl)
+            .and_then(|value| value.as_str().ok().map(CacheControlDirectives::parse))
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the client's `TE` header lists `trailers`, meaning it will accept trailer fields on a chunked
+    /// response.
+    ///
+    /// Pass this on to [`WriteOptions::trailers`](crate::response::WriteOptions::trailers) so that trailers are only
+    /// written to clients that accept them.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// let accepts_trailers = |value: Option<&'static str>| {
+    ///     let env: HashMap<_, _> = value.map(|value| ("HTTP_TE", value)).into_iter().collect();
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap().accepts_trailers()
+    /// };
+    ///
+    /// assert!(accepts_trailers(Some("trailers")));
+    /// assert!(accepts_trailers(Some("gzip;q=0.5, Trailers")));
+    /// assert!(!accepts_trailers(Some("gzip")));
+    /// assert!(!accepts_trailers(None));
+    /// ```
+    pub fn accepts_trailers(&self) -> bool {
+        self.var(MetaVariableKind::HttpTe).is_some_and(|te| {
+            te.as_bytes().split(|&b| b == b',').any(|coding| {
+                let name = coding.split(|&b| b == b';').next().unwrap_or_default();
+                name.trim_ascii().eq_ignore_ascii_case(b"trailers")
+            })
+        })
+    }
+
+    /// Returns the charsets listed in `HTTP_ACCEPT_CHARSET` with their quality values, most preferred first.
+    ///
+    /// Charset names are lowercased, and `*` is returned as listed. Charsets with `q=0` are excluded, so use
+    /// [`CGIRequest::accepts_charset`] to check a specific charset against a list containing `*`. The list is empty if
+    /// the header is unset.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{CGIError, CGIRequest};
+    ///
+    /// let request = |accept_charset: &'static str| {
+    ///     let env = HashMap::from([("HTTP_ACCEPT_CHARSET", accept_charset)]);
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap()
+    /// };
+    ///
+    /// let charsets = request("utf-8, iso-8859-1;q=0.5").accepted_charsets().unwrap();
+    /// assert_eq!(charsets, [("utf-8".to_string(), 1.0), ("iso-8859-1".to_string(), 0.5)]);
+    ///
+    /// let request = request("ISO-8859-1;q=0.5, *;q=0.1, utf-16;q=0");
+    /// assert_eq!(request.accepted_charsets().unwrap(), [("iso-8859-1".to_string(), 0.5), ("*".to_string(), 0.1)]);
+    /// assert!(request.accepts_charset("utf-8"));
+    /// assert!(!request.accepts_charset("UTF-16"));
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(
+    ///     HashMap::from([("HTTP_ACCEPT_CHARSET", "utf-8;q=high")]),
+    ///     &[][..],
+    /// );
+    /// assert!(matches!(request.unwrap().accepted_charsets(), Err(CGIError::InvalidQuality { .. })));
+    /// ```
+    pub fn accepted_charsets(&self) -> Result<Vec<(String, f32)>> {
+        let mut charsets = self
+            .weighted_charsets()?
+            .into_iter()
+            .filter(|(_, q)| *q > 0.0)
+            .collect::<Vec<_>>();
+        charsets.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        Ok(charsets)
     }
 
-    fn request_body_from_env(content_length: usize) -> Result<Vec<u8>> {
-        let mut request_body = vec![0u8; content_length];
-        stdin()
-            .read_exact(&mut request_body)
-            .context(error::ReadRequestBodySnafu)
-            .and(Ok(request_body))
+    /// Returns true if `charset` is acceptable according to `HTTP_ACCEPT_CHARSET`: it is listed with a non-zero
+    /// quality, or it isn't listed and `*` is. Every charset is acceptable if the header is unset or malformed.
+    pub fn accepts_charset(&self, charset: &str) -> bool {
+        let Ok(charsets) = self.weighted_charsets() else {
+            return true;
+        };
+        if charsets.is_empty() {
+            return true;
+        }
+        charsets
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(charset))
+            .or_else(|| charsets.iter().find(|(name, _)| name == "*"))
+            .is_some_and(|(_, q)| *q > 0.0)
+    }
+
+    fn weighted_charsets(&self) -> Result<Vec<(String, f32)>> {
+        let kind = MetaVariableKind::HttpAcceptCharset;
+        let Some(accept_charset) = self.var(kind) else {
+            return Ok(Vec::new());
+        };
+
+        let mut charsets = Vec::new();
+        for item in accept_charset.as_str()?.split(',') {
+            let mut params = item.split(';');
+            let charset = params.next().unwrap_or_default().trim();
+            if charset.is_empty() {
+                continue;
+            }
+            let mut q = 1.0;
+            for param in params {
+                let Some((name, value)) = param.split_once('=') else {
+                    continue;
+                };
+                if name.trim().eq_ignore_ascii_case("q") {
+                    let value = value.trim();
+                    q = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|q| (0.0..=1.0).contains(q))
+                        .context(error::InvalidQualitySnafu { kind, value })?;
+                }
+            }
+            charsets.push((charset.to_ascii_lowercase(), q));
+        }
+        Ok(charsets)
+    }
+
+    /// Reads the body and parses it as an `application/x-www-form-urlencoded` form, returning the decoded key-value
+    /// pairs in order.
+    ///
+    /// The body is parsed for any method, as long as `CONTENT_TYPE` is a urlencoded form; otherwise
+    /// [`CGIError::NotAForm`] is returned.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::{CGIError, CGIRequest};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let body = b"name=Jane+Doe&email=jane%40example.com&subscribed";
+    /// let env = HashMap::from([
+    ///     ("REQUEST_METHOD", "PUT"),
+    ///     ("CONTENT_TYPE", "application/x-www-form-urlencoded; charset=UTF-8"),
+    ///     ("CONTENT_LENGTH", "49"),
+    /// ]);
+    ///
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &body[..]).unwrap();
+    /// let form = request.body_as_form().await.unwrap();
+    /// assert_eq!(
+    ///     form,
+    ///     [
+    ///         ("name".to_string(), "Jane Doe".to_string()),
+    ///         ("email".to_string(), "jane@example.com".to_string()),
+    ///         ("subscribed".to_string(), String::new()),
+    ///     ]
+    /// );
+    ///
+    /// let env = HashMap::from([("REQUEST_METHOD", "PATCH"), ("CONTENT_TYPE", "application/json")]);
+    /// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    /// assert!(matches!(request.body_as_form().await, Err(CGIError::NotAForm { .. })));
+    /// # }
+    /// ```
+    pub async fn body_as_form(self) -> Result<Vec<(String, String)>>
+    where
+        B::Error: Into<BoxError>,
+    {
+        let content_type = self.try_var(MetaVariableKind::ContentType)?;
+        let content_type = content_type.as_str()?;
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        ensure!(
+            mime.eq_ignore_ascii_case("application/x-www-form-urlencoded"),
+            error::NotAFormSnafu {
+                value: content_type
+            }
+        );
+
+        let body = self
+            .request_body
+            .collect()
+            .await
+            .map_err(|e| io::Error::other(e.into()))
+            .context(error::ReadRequestBodySnafu)?
+            .to_bytes();
+
+        Ok(body
+            .split(|&b| b == b'&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut parts = pair.splitn(2, |&b| b == b'=');
+                let key = decode_form_component(parts.next().unwrap_or_default());
+                let value = decode_form_component(parts.next().unwrap_or_default());
+                (key, value)
+            })
+            .collect())
+    }
+
+    /// Evaluates the request's conditional headers against the current `etag` and `last_modified` time of the
+    /// requested resource, following the precedence of RFC 7232 §6.
+    ///
+    /// `etag` is the full entity-tag including its quotes, e.g. `"v2"` or `W/"v2"`. `If-Match` uses the strong
+    /// comparison and `If-None-Match` the weak one. `If-Unmodified-Since` is only considered without `If-Match`, and
+    /// `If-Modified-Since` only without `If-None-Match` and for `GET` or `HEAD`. Unparseable dates are ignored.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{request::Precondition, CGIRequest};
+    ///
+    /// // Sun, 06 Nov 1994 08:49:37 GMT
+    /// let last_modified = UNIX_EPOCH + Duration::from_secs(784111777);
+    /// let evaluate = |method: &'static str, headers: &[(&'static str, &'static str)]| {
+    ///     let mut env = HashMap::from([("REQUEST_METHOD", method)]);
+    ///     env.extend(headers.iter().copied());
+    ///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..])
+    ///         .unwrap()
+    ///         .evaluate_preconditions("\"v2\"", last_modified)
+    /// };
+    ///
+    /// assert_eq!(evaluate("GET", &[]), Precondition::None);
+    /// assert_eq!(evaluate("GET", &[("HTTP_IF_NONE_MATCH", "\"v1\", W/\"v2\"")]), Precondition::NotModified);
+    /// assert_eq!(evaluate("PUT", &[("HTTP_IF_NONE_MATCH", "*")]), Precondition::PreconditionFailed);
+    /// assert_eq!(evaluate("PUT", &[("HTTP_IF_MATCH", "W/\"v2\"")]), Precondition::PreconditionFailed);
+    /// assert_eq!(
+    ///     evaluate("GET", &[("HTTP_IF_MODIFIED_SINCE", "Sun, 06 Nov 1994 08:49:37 GMT")]),
+    ///     Precondition::NotModified
+    /// );
+    ///
+    /// // If-Match takes precedence over If-Unmodified-Since.
+    /// assert_eq!(
+    ///     evaluate("PUT", &[
+    ///         ("HTTP_IF_MATCH", "\"v2\""),
+    ///         ("HTTP_IF_UNMODIFIED_SINCE", "Sat, 01 Jan 1994 00:00:00 GMT"),
+    ///     ]),
+    ///     Precondition::None
+    /// );
+    /// // If-None-Match takes precedence over If-Modified-Since.
+    /// assert_eq!(
+    ///     evaluate("GET", &[
+    ///         ("HTTP_IF_NONE_MATCH", "\"v1\""),
+    ///         ("HTTP_IF_MODIFIED_SINCE", "Sun, 06 Nov 1994 08:49:37 GMT"),
+    ///     ]),
+    ///     Precondition::None
+    /// );
+    /// // A failed If-Unmodified-Since wins over a matching If-None-Match.
+    /// assert_eq!(
+    ///     evaluate("GET", &[
+    ///         ("HTTP_IF_UNMODIFIED_SINCE", "Sat, 01 Jan 1994 00:00:00 GMT"),
+    ///         ("HTTP_IF_NONE_MATCH", "\"v2\""),
+    ///     ]),
+    ///     Precondition::PreconditionFailed
+    /// );
+    /// ```
+    pub fn evaluate_preconditions(&self, etag: &str, last_modified: SystemTime) -> Precondition {
+        let string_var = |kind| {
+            self.var(kind)
+                .and_then(|var| var.as_str().ok().map(str::to_string))
+        };
+        let date_var =
+            |kind| string_var(kind).and_then(|date| httpdate::parse_http_date(&date).ok());
+        // HTTP dates have a resolution of one second.
+        let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let last_modified = seconds(last_modified);
+
+        if let Some(if_match) = string_var(MetaVariableKind::HttpIfMatch) {
+            if !etag_list_matches(&if_match, etag, false) {
+                return Precondition::PreconditionFailed;
+            }
+        } else if let Some(date) = date_var(MetaVariableKind::HttpIfUnmodifiedSince) {
+            if last_modified > seconds(date) {
+                return Precondition::PreconditionFailed;
+            }
+        }
+
+        let is_get_or_head = self
+            .var(MetaVariableKind::RequestMethod)
+            .is_some_and(|method| matches!(method.as_bytes(), b"GET" | b"HEAD"));
+
+        if let Some(if_none_match) = string_var(MetaVariableKind::HttpIfNoneMatch) {
+            if etag_list_matches(&if_none_match, etag, true) {
+                return if is_get_or_head {
+                    Precondition::NotModified
+                } else {
+                    Precondition::PreconditionFailed
+                };
+            }
+        } else if let Some(date) = date_var(MetaVariableKind::HttpIfModifiedSince) {
+            if is_get_or_head && last_modified <= seconds(date) {
+                return Precondition::NotModified;
+            }
+        }
+
+        Precondition::None
     }
 
+    /// Returns the request URI.
+    ///
+    /// `REQUEST_URI` is authoritative when set, and is used verbatim, including its query string. Otherwise the URI
+    /// is built from `SCRIPT_NAME`, `PATH_INFO` and `QUERY_STRING`, omitting the `?` if the query string is unset,
+    /// empty or only whitespace.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::CGIRequest;
+    /// for query_string in [Some(""), Some(" \t"), None] {
+    ///     let mut env = HashMap::from([("SCRIPT_NAME", "/script"), ("PATH_INFO", "/path")]);
+    ///     env.extend(query_string.map(|query_string| ("QUERY_STRING", query_string)));
+    ///
+    ///     let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    ///     assert_eq!(request.uri().unwrap(), "/script/path");
+    /// }
+    /// ```
+    ///
+    /// The URI is rejected with [`CGIError::InvalidUri`] if it contains whitespace, control characters, non-ASCII
+    /// bytes or malformed percent-escapes, so a reflected URI can't be used to split a response.
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// # use cgi_rs::{CGIError, CGIRequest};
+    /// for uri in ["/a b", "/a%zz", "/a%2", "/a\r\nSet-Cookie: x"] {
+    ///     let env = HashMap::from([("REQUEST_URI", uri)]);
+    ///     let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    ///     assert!(matches!(request.uri(), Err(CGIError::InvalidUri { value }) if value == uri));
+    /// }
+    /// ```
     pub fn uri(&self) -> Result<String> {
+        let uri = self.origin_form_uri()?;
+        if !self.absolute_uri {
+            return Ok(uri);
+        }
+        let Some(host) = self.host() else {
+            return Ok(uri);
+        };
+        let host = host.as_str()?;
+        ensure!(
+            !host.is_empty()
+                && host
+                    .bytes()
+                    .all(|b| b.is_ascii_graphic() && !matches!(b, b'/' | b'?' | b'#' | b'@')),
+            error::InvalidHostSnafu { value: host }
+        );
+
+        let scheme = if self.is_secure() { "https" } else { "http" };
+        Ok(format!("{}://{}{}", scheme, host, uri))
+    }
+
+    // The validated path and query of the request.
+    fn origin_form_uri(&self) -> Result<String> {
+        let uri = self.raw_uri()?;
+        ensure!(
+            uri.len() <= self.max_uri_length,
+            error::UriTooLongSnafu {
+                length: uri.len(),
+                limit: self.max_uri_length,
+            }
+        );
+
+        let bytes = uri.as_bytes();
+        let mut position = 0;
+        while position < bytes.len() {
+            match bytes[position] {
+                b'%' => {
+                    let escape = bytes.get(position + 1..position + 3);
+                    if !escape.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                        return error::InvalidUriSnafu { value: uri }.fail();
+                    }
+                    position += 3;
+                }
+                byte if byte.is_ascii_graphic() => position += 1,
+                _ => return error::InvalidUriSnafu { value: uri }.fail(),
+            }
+        }
+
+        Ok(uri)
+    }
+
+    fn raw_uri(&self) -> Result<String> {
         // Some CGI implementations (e.g. Apache) set REQUEST_URI, which isn't in the RFC
         self.var(MetaVariableKind::RequestUri)
             .map(|uri| Ok(uri.as_str()?.to_string()))
             .unwrap_or_else(|| {
 
-                let path_info_str = match MetaVariableKind::PathInfo.try_from_env() {
+                let path_info_str = match self.try_var(MetaVariableKind::PathInfo) {
                     Ok(meta_variable) => {
                        String::from(meta_variable.as_str().unwrap_or(""))
                     }
@@ -65,18 +2002,48 @@ impl <B> CGIRequest<B> where B: Body {
                     }
                 };
 
-                let script_name = MetaVariableKind::ScriptName.try_from_env()?;
-                let query_string = MetaVariableKind::QueryString.try_from_env()?;
-                Ok(format!(
-                    "{}{}?{}",
-                    script_name.as_str()?,
-                    path_info_str,
-                    query_string.as_str()?
-                ))
+                let Some(script_name) = self.var(MetaVariableKind::ScriptName) else {
+                    return self
+                        .default_uri
+                        .clone()
+                        .context(error::CannotDetermineUriSnafu);
+                };
+                let mut uri = format!("{}{}", script_name.as_str()?, path_info_str);
+                if let Some(query_string) = self.var(MetaVariableKind::QueryString) {
+                    let query_string = query_string.as_str()?;
+                    if !query_string.trim().is_empty() {
+                        uri.push('?');
+                        uri.push_str(query_string);
+                    }
+                }
+                Ok(uri)
             })
     }
 }
 
+// Decodes `+` as a space and `%XX` escapes, leaving malformed escapes as they are.
+fn decode_form_component(component: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(component.len());
+    let mut position = 0;
+    while position < component.len() {
+        let escape = component
+            .get(position + 1..position + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (component[position], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                position += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        position += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 macro_rules! try_set_headers {
     ($request_builder:expr, $cgi_request:expr, $([$header:expr, $value:expr]),* $(,)?) => {
         $(
@@ -87,29 +2054,126 @@ macro_rules! try_set_headers {
     };
 }
 
+/// Converts a CGI request into a `hyper` request.
+///
+/// `REQUEST_METHOD` must hold a valid HTTP method token, otherwise [`CGIError::InvalidMethod`] is returned. Extension
+/// methods such as WebDAV's `PROPFIND` are accepted.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// use hyper::Request;
+/// # use hyper::body::Bytes;
+/// # use http_body_util::Full;
+/// # use cgi_rs::{CGIError, CGIRequest};
+/// let to_request = |method: &'static str| {
+///     let env = HashMap::from([("REQUEST_METHOD", method), ("REQUEST_URI", "/")]);
+///     CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).and_then(Request::try_from)
+/// };
+///
+/// assert_eq!(to_request("PROPFIND").unwrap().method(), "PROPFIND");
+/// assert!(matches!(to_request("GE T"), Err(CGIError::InvalidMethod { value }) if value == "GE T"));
+/// ```
+///
+/// Content negotiation headers, `Host`, `User-Agent`, `Cookie`, `Referer` and `Authorization` are hoisted from
+/// their `HTTP_*` meta-variables:
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use hyper::{body::Bytes, Request};
+/// # use http_body_util::Full;
+/// # use cgi_rs::CGIRequest;
+/// let env = HashMap::from([
+///     ("REQUEST_METHOD", "GET"),
+///     ("REQUEST_URI", "/"),
+///     ("HTTP_ACCEPT_ENCODING", "gzip, br"),
+///     ("HTTP_ACCEPT_LANGUAGE", "en-GB, en;q=0.8"),
+///     ("HTTP_ACCEPT_CHARSET", "utf-8"),
+///     ("HTTP_REFERER", "https://example.com/"),
+/// ]);
+/// let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).and_then(Request::try_from).unwrap();
+///
+/// assert_eq!(request.headers()["Accept-Encoding"], "gzip, br");
+/// assert_eq!(request.headers()["Accept-Language"], "en-GB, en;q=0.8");
+/// assert_eq!(request.headers()["Accept-Charset"], "utf-8");
+/// assert_eq!(request.headers()["Referer"], "https://example.com/");
+/// ```
 impl <B>TryFrom<CGIRequest<B>> for Request<B> where B: Body {
     type Error = CGIError;
 
     fn try_from(cgi_request: CGIRequest<B>) -> Result<Self> {
+        let method = cgi_request.var(MetaVariableKind::RequestMethod);
+        let is_empty = method
+            .as_ref()
+            .is_some_and(|method| method.as_bytes().is_empty());
+        let method =
+            match (method, &cgi_request.default_method) {
+                (Some(method), _) if !is_empty => Method::from_bytes(method.as_bytes())
+                    .ok()
+                    .context(error::InvalidMethodSnafu {
+                        value: method.value.to_string_lossy(),
+                    })?,
+                (_, Some(default)) => default.clone(),
+                (Some(_), None) => return error::EmptyMethodSnafu.fail(),
+                (None, None) => {
+                    return error::MetaVariableNotSetSnafu {
+                        kind: MetaVariableKind::RequestMethod,
+                    }
+                    .fail()
+                }
+            };
 
-        let mut request_builder = Request::builder()
-            .method(
-                cgi_request
-                    .try_var(MetaVariableKind::RequestMethod)?
-                    .as_bytes(),
-            )
-            .uri(cgi_request.uri()?);
+        let mut request_builder = Request::builder().method(method).uri(cgi_request.uri()?);
+
+        if let (Some(truncated_body), Some(extensions)) =
+            (cgi_request.truncated_body, request_builder.extensions_mut())
+        {
+            extensions.insert(truncated_body);
+        }
+
+        if let (Some(auth), Some(extensions)) =
+            (cgi_request.auth(), request_builder.extensions_mut())
+        {
+            extensions.insert(auth);
+        }
+
+        if let (Some(ip), Some(extensions)) =
+            (cgi_request.remote_ip(), request_builder.extensions_mut())
+        {
+            match cgi_request.remote_port() {
+                Some(port) => {
+                    extensions.insert(RemotePeer(SocketAddr::new(ip, port)));
+                }
+                None => {
+                    extensions.insert(ip);
+                }
+            }
+        }
+
+        // Hoist the normalized length rather than the raw variable, which may be padded.
+        if let Some(content_length) = CGIRequest::<B>::content_length(&*cgi_request.env)? {
+            request_builder = request_builder.header("Content-Length", content_length);
+        }
 
         try_set_headers!(
             request_builder,
             cgi_request,
-            ["Content-Length", MetaVariableKind::ContentLength],
             ["Accept", MetaVariableKind::HttpAccept],
+            ["Accept-Charset", MetaVariableKind::HttpAcceptCharset],
+            ["Accept-Encoding", MetaVariableKind::HttpAcceptEncoding],
+            ["Accept-Language", MetaVariableKind::HttpAcceptLanguage],
+            ["Referer", MetaVariableKind::HttpReferer],
+            ["Authorization", MetaVariableKind::HttpAuthorization],
             ["Host", MetaVariableKind::HttpHost],
             ["User-Agent", MetaVariableKind::HttpUserAgent],
             ["Cookie", MetaVariableKind::HttpCookie],
         );
 
+        for header in &cgi_request.forwarded_headers {
+            if let Some(value) = cgi_request.raw_header(header.as_str()) {
+                request_builder = request_builder.header(header, value);
+            }
+        }
+
         request_builder
             .body(cgi_request.request_body)
             .context(error::RequestParseSnafu)
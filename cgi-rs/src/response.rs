@@ -1,63 +1,1839 @@
-use crate::{error, CGIError, Result};
-use hyper::{http::HeaderValue, HeaderMap, Response};
-use snafu::ResultExt;
-use std::io::Write;
+use crate::request::{etag_list_matches, TraceContext};
+use crate::{error, CGIRequest, MetaVariableKind, Result};
 use bytes::Bytes;
-use http_body_util::{Full};
-use hyper::body::{Body};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Collected};
+use hyper::body::{Body, Buf};
+use hyper::header::{
+    CONNECTION, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG,
+    LOCATION, REFERRER_POLICY, RETRY_AFTER, STRICT_TRANSPORT_SECURITY, TRANSFER_ENCODING, VARY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use hyper::{header::HeaderName, http::HeaderValue, HeaderMap, Response, StatusCode};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::io::{BufWriter, Write};
+use std::time::{Duration, SystemTime};
+
+// The default cap on the size of the status line and headers.
+const DEFAULT_MAX_HEADER_BLOCK_SIZE: usize = 64 * 1024;
+
+/// How header names are cased when a [`CGIResponse`] is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// Write names lowercased, as `HeaderName` stores them (e.g. `content-type`), matching HTTP/2.
+    #[default]
+    Lowercase,
+    /// Capitalize the first letter of each `-`-separated word (e.g. `Content-Type`).
+    TitleCase,
+}
+
+impl HeaderCase {
+    fn apply(self, name: &str) -> String {
+        match self {
+            HeaderCase::Lowercase => name.to_string(),
+            HeaderCase::TitleCase => name
+                .split('-')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>()
+                .join("-"),
+        }
+    }
+}
+
+/// The line terminator written after the status line and each header.
+///
+/// The blank line separating the headers from the body uses the same terminator.
+///
+/// ```rust
+/// use cgi_rs::{response::LineEnding, CGIResponse};
+///
+/// for (line_ending, separator) in [(LineEnding::Crlf, &b"\r\n\r\n"[..]), (LineEnding::Lf, &b"\n\n"[..])] {
+///     let mut response = CGIResponse::uri_too_long();
+///     response.options.line_ending = line_ending;
+///
+///     let mut output = Vec::new();
+///     response.write_response_to_output(&mut output).unwrap();
+///     let body_start = output.len() - "URI Too Long".len();
+///     assert_eq!(&output[body_start - separator.len()..body_start], separator);
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\r\n`, as RFC 3875 §6 requires.
+    #[default]
+    Crlf,
+    /// A bare `\n`, for servers which expect it.
+    Lf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+        }
+    }
+}
+
+/// Controls how a [`CGIResponse`] is serialized.
+///
+/// ```rust
+/// use cgi_rs::{CGIError, CGIResponse};
+/// use hyper::http::HeaderValue;
+///
+/// let mut response = CGIResponse::too_many_requests(std::time::Duration::from_secs(1), None, None);
+/// response.options.max_header_block_size = 32;
+/// response.headers.insert("X-Padding", HeaderValue::from_static("a header that won't fit"));
+///
+/// let mut output = Vec::new();
+/// let result = response.write_response_to_output(&mut output);
+/// assert!(matches!(result, Err(CGIError::HeaderBlockTooLarge { limit: 32, .. })));
+/// assert!(output.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// The maximum size in bytes of the status line and headers, including the blank line ending them. Responses
+    /// exceeding this fail with [`CGIError::HeaderBlockTooLarge`](crate::CGIError::HeaderBlockTooLarge) before
+    /// anything is written. Defaults to 64KiB.
+    pub max_header_block_size: usize,
+    /// How header names are cased. Defaults to [`HeaderCase::Lowercase`].
+    ///
+    /// ```rust
+    /// use cgi_rs::{response::HeaderCase, CGIResponse};
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// let mut output = Vec::new();
+    /// response.options.header_case = HeaderCase::Lowercase;
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"Status: 414 URI Too Long\r\ncontent-type: text/plain\r\n"));
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// let mut output = Vec::new();
+    /// response.options.header_case = HeaderCase::TitleCase;
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"Status: 414 URI Too Long\r\nContent-Type: text/plain\r\n"));
+    /// ```
+    pub header_case: HeaderCase,
+    /// The line terminator, including for the blank line separating the headers from the body. Defaults to
+    /// [`LineEnding::Crlf`].
+    ///
+    /// ```rust
+    /// use cgi_rs::{response::LineEnding, CGIResponse};
+    ///
+    /// let mut output = Vec::new();
+    /// CGIResponse::uri_too_long().write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"Status: 414 URI Too Long\r\ncontent-type: text/plain\r\ncontent-length: 12\r\n\r\nURI Too Long"
+    /// );
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// response.options.line_ending = LineEnding::Lf;
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"Status: 414 URI Too Long\ncontent-type: text/plain\ncontent-length: 12\n\nURI Too Long"
+    /// );
+    /// ```
+    pub line_ending: LineEnding,
+    /// Write the response for a non-parsed-header (`nph-*`) script, which the server passes to the client unmodified.
+    ///
+    /// The first line is then a full HTTP status line using [`WriteOptions::server_protocol`] instead of a `Status`
+    /// header, and redirects are written as-is rather than left for the server to process. Defaults to `false`.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// response.options.nph = true;
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"HTTP/1.1 414 URI Too Long\r\ncontent-type: text/plain\r\n"));
+    /// ```
+    pub nph: bool,
+    /// Write the trailer fields of a streamed NPH body, which also frames the body with
+    /// `Transfer-Encoding: chunked`. Set this from [`CGIRequest::accepts_trailers`]; when it is `false`, or the
+    /// response isn't [`WriteOptions::nph`], trailers are dropped. Defaults to `false`.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use http_body_util::{BodyExt, Full};
+    /// use hyper::{body::Bytes, HeaderMap, Response};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let trailers = HeaderMap::from_iter([("server-timing".parse().unwrap(), "db;dur=5".parse().unwrap())]);
+    /// let body = Full::new(Bytes::from("hello")).with_trailers(async { Some(Ok(trailers)) });
+    ///
+    /// let mut response = CGIResponse::from_streaming(Response::new(body));
+    /// response.options.nph = true;
+    /// response.options.trailers = true;
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_streaming_response_to_output(&mut output).await.unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\nserver-timing: db;dur=5\r\n\r\n"
+    /// );
+    /// # }
+    /// ```
+    pub trailers: bool,
+    /// The protocol written in the status line of NPH responses. Defaults to `HTTP/1.1`; scripts can use
+    /// `SERVER_PROTOCOL` to match the request.
+    pub server_protocol: String,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            max_header_block_size: DEFAULT_MAX_HEADER_BLOCK_SIZE,
+            header_case: HeaderCase::default(),
+            line_ending: LineEnding::default(),
+            nph: false,
+            trailers: false,
+            server_protocol: String::from("HTTP/1.1"),
+        }
+    }
+}
+
+impl WriteOptions {
+    // A data frame framed as a chunk (RFC 9112 §7.1). Empty frames are skipped, since an empty chunk ends the body.
+    fn chunk(&self, data: &[u8]) -> Vec<u8> {
+        if data.is_empty() {
+            return Vec::new();
+        }
+        let line_ending = self.line_ending.as_str();
+        let mut chunk = format!("{:x}{}", data.len(), line_ending).into_bytes();
+        chunk.extend(data);
+        chunk.extend(line_ending.as_bytes());
+        chunk
+    }
+
+    // The last chunk, followed by the trailer fields and the blank line ending the body.
+    fn last_chunk(&self, trailers: &HeaderMap) -> Result<Vec<u8>> {
+        let line_ending = self.line_ending.as_str();
+        let mut last_chunk = format!("0{}", line_ending).into_bytes();
+        for (key, value) in trailers {
+            ensure!(
+                !value.as_bytes().iter().any(|&b| b == b'\r' || b == b'\n'),
+                error::InvalidHeaderValueSnafu { name: key.as_str() }
+            );
+            last_chunk.extend(format!("{}: ", self.header_case.apply(key.as_str())).into_bytes());
+            last_chunk.extend(value.as_bytes());
+            last_chunk.extend(line_ending.as_bytes());
+        }
+        last_chunk.extend(line_ending.as_bytes());
+        Ok(last_chunk)
+    }
+}
+
+// The error page formats, in order of preference when the client accepts several equally.
+const ERROR_FORMATS: [&str; 3] = ["text/plain", "application/json", "text/html"];
+
+// Picks the error page format with the highest quality in an `Accept` header, per RFC 9110 §12.5.1. More specific
+// media ranges take precedence over wildcards.
+fn preferred_error_format(accept: &str) -> &'static str {
+    let quality = |format: &str| {
+        let (format_type, _) = format.split_once('/').unwrap_or_default();
+        let mut best: Option<(u8, f32)> = None;
+        for range in accept.split(',') {
+            let mut params = range.split(';');
+            let media_range = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let specificity = if media_range == format {
+                2
+            } else if media_range == format!("{}/*", format_type) {
+                1
+            } else if media_range == "*/*" {
+                0
+            } else {
+                continue;
+            };
+            if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+                best = Some((specificity, q));
+            }
+        }
+        best.map_or(0.0, |(_, q)| q)
+    };
+
+    let mut preferred = (ERROR_FORMATS[0], quality(ERROR_FORMATS[0]));
+    for format in &ERROR_FORMATS[1..] {
+        let q = quality(format);
+        if q > preferred.1 {
+            preferred = (format, q);
+        }
+    }
+    preferred.0
+}
+
+// Whether a `Content-Type` holds text: `text/*`, or a JSON, XML or JavaScript type.
+fn is_text_content_type(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    media_type.starts_with("text/")
+        || media_type.ends_with("+json")
+        || media_type.ends_with("+xml")
+        || [
+            "application/json",
+            "application/xml",
+            "application/javascript",
+        ]
+        .contains(&media_type.as_str())
+}
+
+// Formats `time` as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> HeaderValue {
+    HeaderValue::try_from(httpdate::fmt_http_date(time))
+        .expect("HTTP dates are valid header characters")
+}
+
+/// An error produced by a streaming response body.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The body of a [`CGIResponse`].
+#[derive(Debug)]
+pub enum ResponseBody {
+    /// A body held entirely in memory.
+    Buffered(Bytes),
+    /// A body which is polled for frames as it is written, so it never needs to be held in memory all at once.
+    Stream(UnsyncBoxBody<Bytes, BoxError>),
+}
+
+impl ResponseBody {
+    /// Returns the body's bytes, collecting a streaming body into memory if needed.
+    pub async fn to_bytes(self) -> Result<Bytes> {
+        match self {
+            ResponseBody::Buffered(bytes) => Ok(bytes),
+            ResponseBody::Stream(body) => body
+                .collect()
+                .await
+                .map(Collected::to_bytes)
+                .context(error::ReadResponseBodySnafu),
+        }
+    }
+}
+
+impl Default for ResponseBody {
+    fn default() -> Self {
+        ResponseBody::Buffered(Bytes::new())
+    }
+}
+
+impl From<Bytes> for ResponseBody {
+    fn from(bytes: Bytes) -> Self {
+        ResponseBody::Buffered(bytes)
+    }
+}
 
 #[derive(Debug)]
 pub struct CGIResponse {
     pub headers: HeaderMap<HeaderValue>,
+    /// The status code. An empty status is written as `200`, the default for a CGI response without a `Status` field.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// let mut response = CGIResponse::new(StatusCode::OK);
+    /// response.status = String::new();
+    /// response.reason = None;
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"Status: 200 OK\r\n"));
+    /// ```
     pub status: String,
     pub reason: Option<String>,
-    pub body: Bytes,
+    pub body: ResponseBody,
+    pub options: WriteOptions,
 }
 
 impl CGIResponse {
-    pub async fn write_response_to_output(self, mut output: impl Write) -> Result<()> {
-        self.write_status(&mut output).await?;
-        self.write_headers(&mut output).await?;
-        self.write_body(&mut output).await?;
+    /// Returns a builder for a response, which defaults to `200 OK` with no headers and an empty body.
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    ///
+    /// let response = CGIResponse::builder()
+    ///     .status(404)
+    ///     .header("Content-Type", "text/plain")
+    ///     .body("no such page")
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(response.reason.as_deref(), Some("Not Found"));
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"Status: 404 Not Found\r\ncontent-type: text/plain\r\ncontent-length: 12\r\n\r\nno such page"
+    /// );
+    ///
+    /// assert!(CGIResponse::builder().status(1000).build().is_err());
+    /// assert!(matches!(
+    ///     CGIResponse::builder().header("Content Type", "text/plain").build(),
+    ///     Err(CGIError::InvalidHeaderName { name }) if name == "Content Type"
+    /// ));
+    /// ```
+    pub fn builder() -> CGIResponseBuilder {
+        CGIResponseBuilder {
+            inner: Ok(Self::new(StatusCode::OK)),
+        }
+    }
+
+    /// Creates an empty response with the given status.
+    ///
+    /// The canonical reason phrase is looked up once here and stored in [`CGIResponse::reason`], so it is written
+    /// exactly once after the status code.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// let response = CGIResponse::new(StatusCode::NOT_FOUND);
+    /// assert_eq!(response.status, "404");
+    /// assert_eq!(response.reason.as_deref(), Some("Not Found"));
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"Status: 404 Not Found\r\n"));
+    /// ```
+    pub fn new(status: StatusCode) -> CGIResponse {
+        CGIResponse {
+            headers: HeaderMap::new(),
+            status: status.as_str().to_string(),
+            reason: status.canonical_reason().map(|s| s.to_string()),
+            body: ResponseBody::default(),
+            options: WriteOptions::default(),
+        }
+    }
+
+    /// Creates a response with the given status and its canonical reason as a plain text body.
+    pub(crate) fn status_page(status: StatusCode) -> CGIResponse {
+        Self::error_page(status, None)
+    }
+
+    /// Creates an error response with the given status, describing it in whichever of plain text, JSON or HTML the
+    /// client prefers according to its `Accept` header.
+    ///
+    /// Plain text is used when `accept` is `None` or doesn't prefer either of the others.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let error_page = |accept| async move {
+    ///     let response = CGIResponse::error_page(StatusCode::NOT_FOUND, accept);
+    ///     let content_type = response.headers["Content-Type"].to_str().unwrap().to_string();
+    ///     (content_type, response.body.to_bytes().await.unwrap())
+    /// };
+    ///
+    /// assert_eq!(error_page(None).await, ("text/plain".to_string(), "Not Found".into()));
+    /// assert_eq!(
+    ///     error_page(Some("application/json")).await,
+    ///     ("application/json".to_string(), r#"{"status":404,"title":"Not Found"}"#.into())
+    /// );
+    /// assert_eq!(
+    ///     error_page(Some("text/html,application/xhtml+xml,*/*;q=0.8")).await,
+    ///     ("text/html".to_string(), "<!DOCTYPE html>\n<title>404 Not Found</title>\n<h1>Not Found</h1>\n".into())
+    /// );
+    /// assert_eq!(error_page(Some("application/json;q=0.5, text/*")).await.0, "text/plain");
+    /// assert_eq!(error_page(Some("image/png")).await.0, "text/plain");
+    /// # }
+    /// ```
+    pub fn error_page(status: StatusCode, accept: Option<&str>) -> CGIResponse {
+        let mut response = Self::new(status);
+        let message = response
+            .reason
+            .clone()
+            .unwrap_or_else(|| response.status.clone());
+
+        let (content_type, body) = match accept.map(preferred_error_format) {
+            Some("application/json") => (
+                "application/json",
+                format!(r#"{{"status":{},"title":"{}"}}"#, status.as_u16(), message),
+            ),
+            Some("text/html") => (
+                "text/html",
+                format!(
+                    "<!DOCTYPE html>\n<title>{} {}</title>\n<h1>{}</h1>\n",
+                    status.as_u16(),
+                    message,
+                    message
+                ),
+            ),
+            _ => ("text/plain", message),
+        };
+
+        response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response.body = Bytes::from(body).into();
+        response
+    }
+
+    /// Creates a `414 URI Too Long` response, for requests rejected with
+    /// [`CGIError::UriTooLong`](crate::CGIError::UriTooLong).
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let response = CGIResponse::uri_too_long();
+    /// assert_eq!(response.status, "414");
+    /// assert_eq!(response.reason.as_deref(), Some("URI Too Long"));
+    /// ```
+    pub fn uri_too_long() -> CGIResponse {
+        Self::status_page(StatusCode::URI_TOO_LONG)
+    }
+
+    /// Creates a `408 Request Timeout` response with `Connection: close`, for requests whose body the client didn't
+    /// send in time. The connection can't be reused, as the rest of the body may still arrive on it.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let response = CGIResponse::request_timeout();
+    /// assert_eq!(response.status, "408");
+    /// assert_eq!(response.headers["Connection"], "close");
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.starts_with(b"Status: 408 Request Timeout\r\n"));
+    /// assert!(output.ends_with(b"\r\n\r\nRequest Timeout"));
+    /// ```
+    pub fn request_timeout() -> CGIResponse {
+        let mut response = Self::status_page(StatusCode::REQUEST_TIMEOUT);
+        response
+            .headers
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+        response
+    }
+
+    /// Creates a `403 Forbidden` response, for requests rejected because
+    /// [`CGIRequest::is_same_site`](crate::CGIRequest::is_same_site) is `Some(false)`.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let response = CGIResponse::forbidden_cross_site();
+    /// assert_eq!(response.status, "403");
+    /// assert_eq!(response.headers["Vary"], "Sec-Fetch-Site");
+    /// ```
+    pub fn forbidden_cross_site() -> CGIResponse {
+        let mut response = Self::status_page(StatusCode::FORBIDDEN);
+        response
+            .headers
+            .insert(VARY, HeaderValue::from_static("Sec-Fetch-Site"));
+        response
+    }
+
+    /// Creates a response to a `GET` or `HEAD` request for content identified by `etag`.
+    ///
+    /// If the request's `If-None-Match` lists `etag` (by weak comparison) the response is an empty `304 Not Modified`,
+    /// otherwise it's a `200 OK` with `body`. Both carry the `ETag` header. For validation against `Last-Modified` as
+    /// well, use [`CGIRequest::evaluate_preconditions`](crate::CGIRequest::evaluate_preconditions).
+    ///
+    /// ```rust
+    /// # use std::collections::HashMap;
+    /// # use hyper::body::Bytes;
+    /// # use http_body_util::Full;
+    /// use cgi_rs::{CGIRequest, CGIResponse};
+    /// use hyper::http::HeaderValue;
+    ///
+    /// let respond = |if_none_match: Option<&'static str>| {
+    ///     let mut env = HashMap::from([("REQUEST_METHOD", "GET")]);
+    ///     env.extend(if_none_match.map(|tags| ("HTTP_IF_NONE_MATCH", tags)));
+    ///     let request = CGIRequest::<Full<Bytes>>::from_source_with_reader(env, &[][..]).unwrap();
+    ///     CGIResponse::cached("Hello", HeaderValue::from_static("\"v2\""), &request)
+    /// };
+    ///
+    /// // A cache hit.
+    /// let response = respond(Some("\"v1\", W/\"v2\""));
+    /// assert_eq!(response.status, "304");
+    /// assert_eq!(response.headers["ETag"], "\"v2\"");
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert!(output.ends_with(b"\r\n\r\n"));
+    ///
+    /// // A cache miss.
+    /// for if_none_match in [None, Some("\"v1\"")] {
+    ///     let response = respond(if_none_match);
+    ///     assert_eq!(response.status, "200");
+    ///     assert_eq!(response.headers["ETag"], "\"v2\"");
+    ///     let mut output = Vec::new();
+    ///     response.write_response_to_output(&mut output).unwrap();
+    ///     assert!(output.ends_with(b"\r\n\r\nHello"));
+    /// }
+    /// ```
+    pub fn cached<B>(
+        body: impl Into<Bytes>,
+        etag: HeaderValue,
+        request: &CGIRequest<B>,
+    ) -> CGIResponse
+    where
+        B: hyper::body::Body,
+    {
+        let is_cached = etag.to_str().is_ok_and(|etag| {
+            request
+                .var(MetaVariableKind::HttpIfNoneMatch)
+                .is_some_and(|var| {
+                    var.as_str()
+                        .is_ok_and(|tags| etag_list_matches(tags, etag, true))
+                })
+        });
+
+        let mut response = if is_cached {
+            Self::new(StatusCode::NOT_MODIFIED)
+        } else {
+            let mut response = Self::new(StatusCode::OK);
+            response.body = body.into().into();
+            response
+        };
+        response.headers.insert(ETAG, etag);
+        response
+    }
+
+    /// Creates a redirect to `location` with the given (typically `3xx`) status.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::{http::HeaderValue, StatusCode};
+    ///
+    /// let response = CGIResponse::redirect(HeaderValue::from_static("https://example.com/login"), StatusCode::FOUND);
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"Status: 302 Found\r\nlocation: https://example.com/login\r\ncontent-length: 0\r\n\r\n"
+    /// );
+    /// ```
+    pub fn redirect(location: HeaderValue, status: StatusCode) -> CGIResponse {
+        let mut response = Self::new(status);
+        response.headers.insert(LOCATION, location);
+        response
+    }
+
+    /// Creates an `application/problem+json` response describing an error, per RFC 9457.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let response = CGIResponse::problem(
+    ///     StatusCode::FORBIDDEN,
+    ///     "https://example.com/probs/out-of-credit",
+    ///     "You do not have enough credit.",
+    ///     "Your current balance is 30, but that costs 50.",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(response.status, "403");
+    /// assert_eq!(response.headers["Content-Type"], "application/problem+json");
+    ///
+    /// let body: serde_json::Value = serde_json::from_slice(&response.body.to_bytes().await.unwrap()).unwrap();
+    /// assert_eq!(
+    ///     body,
+    ///     serde_json::json!({
+    ///         "type": "https://example.com/probs/out-of-credit",
+    ///         "title": "You do not have enough credit.",
+    ///         "status": 403,
+    ///         "detail": "Your current balance is 30, but that costs 50.",
+    ///     })
+    /// );
+    /// # }
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn problem(
+        status: StatusCode,
+        type_uri: &str,
+        title: &str,
+        detail: &str,
+    ) -> Result<CGIResponse> {
+        let body = serde_json::to_vec(&serde_json::json!({
+            "type": type_uri,
+            "title": title,
+            "status": status.as_u16(),
+            "detail": detail,
+        }))
+        .context(error::SerializeJsonSnafu)?;
+
+        let mut response = Self::new(status);
+        response.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        response.body = Bytes::from(body).into();
+        Ok(response)
+    }
+
+    /// Creates a `429 Too Many Requests` response.
+    ///
+    /// `Retry-After` is set to `retry_after` in whole seconds, rounded up. `X-RateLimit-Limit` and
+    /// `X-RateLimit-Remaining` are set when provided.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let response = CGIResponse::too_many_requests(Duration::from_millis(2500), Some(100), Some(0));
+    /// assert_eq!(response.status, "429");
+    /// assert_eq!(response.headers["Retry-After"], "3");
+    /// assert_eq!(response.headers["X-RateLimit-Limit"], "100");
+    /// assert_eq!(response.headers["X-RateLimit-Remaining"], "0");
+    /// ```
+    pub fn too_many_requests(
+        retry_after: Duration,
+        limit: Option<u64>,
+        remaining: Option<u64>,
+    ) -> CGIResponse {
+        let mut seconds = retry_after.as_secs();
+        if retry_after.subsec_nanos() > 0 {
+            seconds += 1;
+        }
+        Self::rate_limited(HeaderValue::from(seconds), limit, remaining)
+    }
+
+    /// Creates a `429 Too Many Requests` response like [`CGIResponse::too_many_requests`], with `Retry-After` set to
+    /// the HTTP-date `retry_at` instead of a number of seconds.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let retry_at = UNIX_EPOCH + Duration::from_secs(784111777);
+    /// let response = CGIResponse::too_many_requests_until(retry_at, Some(100), None);
+    /// assert_eq!(response.status, "429");
+    /// assert_eq!(response.headers["Retry-After"], "Sun, 06 Nov 1994 08:49:37 GMT");
+    /// assert_eq!(response.headers["X-RateLimit-Limit"], "100");
+    /// ```
+    pub fn too_many_requests_until(
+        retry_at: SystemTime,
+        limit: Option<u64>,
+        remaining: Option<u64>,
+    ) -> CGIResponse {
+        Self::rate_limited(http_date(retry_at), limit, remaining)
+    }
+
+    /// Creates a `503 Service Unavailable` response, with `Retry-After` set to the HTTP-date `retry_at`, e.g. the end
+    /// of a maintenance window.
+    ///
+    /// ```rust
+    /// use std::time::{Duration, UNIX_EPOCH};
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let retry_at = UNIX_EPOCH + Duration::from_secs(784111777);
+    /// let response = CGIResponse::service_unavailable_until(retry_at);
+    /// assert_eq!(response.status, "503");
+    /// assert_eq!(response.headers["Retry-After"], "Sun, 06 Nov 1994 08:49:37 GMT");
+    /// ```
+    pub fn service_unavailable_until(retry_at: SystemTime) -> CGIResponse {
+        let mut response = Self::new(StatusCode::SERVICE_UNAVAILABLE);
+        response.headers.insert(RETRY_AFTER, http_date(retry_at));
+        response
+    }
+
+    fn rate_limited(
+        retry_after: HeaderValue,
+        limit: Option<u64>,
+        remaining: Option<u64>,
+    ) -> CGIResponse {
+        let mut response = Self::new(StatusCode::TOO_MANY_REQUESTS);
+        response.headers.insert(RETRY_AFTER, retry_after);
+
+        if let Some(limit) = limit {
+            response
+                .headers
+                .insert("X-RateLimit-Limit", HeaderValue::from(limit));
+        }
+        if let Some(remaining) = remaining {
+            response
+                .headers
+                .insert("X-RateLimit-Remaining", HeaderValue::from(remaining));
+        }
+
+        response
+    }
+
+    /// Sets `Strict-Transport-Security`, telling browsers to only contact this host over HTTPS for `max_age` (in whole
+    /// seconds).
+    ///
+    /// Browsers ignore the header on plain HTTP responses, so only set it when
+    /// [`CGIRequest::is_secure`](crate::CGIRequest::is_secure) is true, and redirect insecure requests with
+    /// [`CGIRequest::require_https`](crate::CGIRequest::require_https) instead. `preload` should only be used once
+    /// the host qualifies for browsers' preload lists, as removal from them is slow.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// let hsts = |include_subdomains, preload| {
+    ///     let mut response = CGIResponse::redirect("/".parse().unwrap(), StatusCode::FOUND);
+    ///     response.with_hsts(Duration::from_secs(31536000), include_subdomains, preload);
+    ///     response.headers["Strict-Transport-Security"].clone()
+    /// };
+    ///
+    /// assert_eq!(hsts(false, false), "max-age=31536000");
+    /// assert_eq!(hsts(true, false), "max-age=31536000; includeSubDomains");
+    /// assert_eq!(hsts(true, true), "max-age=31536000; includeSubDomains; preload");
+    /// assert_eq!(hsts(false, true), "max-age=31536000; preload");
+    /// ```
+    pub fn with_hsts(
+        &mut self,
+        max_age: Duration,
+        include_subdomains: bool,
+        preload: bool,
+    ) -> &mut Self {
+        let mut value = format!("max-age={}", max_age.as_secs());
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if preload {
+            value.push_str("; preload");
+        }
+
+        self.headers.insert(
+            STRICT_TRANSPORT_SECURITY,
+            HeaderValue::try_from(value).expect("HSTS directives are valid header characters"),
+        );
+        self
+    }
+
+    /// Sets a baseline of security headers, keeping any of them that are already set:
+    ///
+    /// - `X-Content-Type-Options: nosniff`
+    /// - `X-Frame-Options: DENY`
+    /// - `Referrer-Policy: no-referrer`
+    /// - `Content-Security-Policy: default-src 'self'; frame-ancestors 'none'`
+    ///
+    /// Set a header before calling this to override its default, or after to replace it.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let mut response = CGIResponse::builder()
+    ///     .header("Referrer-Policy", "strict-origin-when-cross-origin")
+    ///     .build()
+    ///     .unwrap();
+    /// response.secure_defaults();
+    ///
+    /// assert_eq!(response.headers["X-Content-Type-Options"], "nosniff");
+    /// assert_eq!(response.headers["X-Frame-Options"], "DENY");
+    /// assert_eq!(response.headers["Referrer-Policy"], "strict-origin-when-cross-origin");
+    /// assert_eq!(
+    ///     response.headers["Content-Security-Policy"],
+    ///     "default-src 'self'; frame-ancestors 'none'"
+    /// );
+    /// ```
+    pub fn secure_defaults(&mut self) -> &mut Self {
+        let defaults = [
+            (X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            (X_FRAME_OPTIONS, "DENY"),
+            (REFERRER_POLICY, "no-referrer"),
+            (
+                CONTENT_SECURITY_POLICY,
+                "default-src 'self'; frame-ancestors 'none'",
+            ),
+        ];
+        for (name, value) in defaults {
+            self.headers
+                .entry(name)
+                .or_insert(HeaderValue::from_static(value));
+        }
+        self
+    }
+
+    /// Sets the `traceparent` and, if `context` has one, `tracestate` headers from `context`, so downstream systems
+    /// can correlate this response with the trace. The `traceparent` is written in version `00` format.
+    ///
+    /// ```rust
+    /// use cgi_rs::{request::TraceContext, CGIResponse};
+    /// use hyper::StatusCode;
+    ///
+    /// let context = TraceContext {
+    ///     version: 0,
+    ///     trace_id: 0x0af7651916cd43dd8448eb211c80319c,
+    ///     parent_id: 0xb7ad6b7169203331,
+    ///     flags: 1,
+    ///     tracestate: Some("congo=t61rcWkgMzE".to_string()),
+    /// };
+    /// let mut response = CGIResponse::new(StatusCode::OK);
+    /// response.with_trace_context(&context);
+    ///
+    /// assert_eq!(response.headers["traceparent"], "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01");
+    /// assert_eq!(response.headers["tracestate"], "congo=t61rcWkgMzE");
+    ///
+    /// let context = TraceContext { trace_id: 1, parent_id: 2, flags: 0, tracestate: None, ..context };
+    /// let mut response = CGIResponse::new(StatusCode::OK);
+    /// response.with_trace_context(&context);
+    /// assert_eq!(response.headers["traceparent"], "00-00000000000000000000000000000001-0000000000000002-00");
+    /// assert!(!response.headers.contains_key("tracestate"));
+    /// ```
+    pub fn with_trace_context(&mut self, context: &TraceContext) -> &mut Self {
+        let traceparent = format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            context.trace_id, context.parent_id, context.flags
+        );
+        self.headers.insert(
+            HeaderName::from_static("traceparent"),
+            HeaderValue::try_from(traceparent).expect("traceparent is hex digits and dashes"),
+        );
+
+        let tracestate = context
+            .tracestate
+            .as_deref()
+            .and_then(|tracestate| HeaderValue::from_str(tracestate).ok());
+        if let Some(tracestate) = tracestate {
+            self.headers
+                .insert(HeaderName::from_static("tracestate"), tracestate);
+        }
+        self
+    }
+
+    /// Prepends a UTF-8 byte order mark to the body, for legacy clients which need it to detect the encoding, such as
+    /// spreadsheet applications opening CSV files.
+    ///
+    /// Only text-like bodies (`text/*`, JSON, XML and JavaScript) are changed, and only if they're buffered and don't
+    /// already start with a BOM. A `Content-Length` header is updated to include the three bytes of the BOM.
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIResponse, ResponseBody};
+    ///
+    /// let csv = |content_type: &str| {
+    ///     let mut response = CGIResponse::builder()
+    ///         .header("Content-Type", content_type)
+    ///         .header("Content-Length", "6")
+    ///         .body("a,b,c\n")
+    ///         .build()
+    ///         .unwrap();
+    ///     response.with_bom().with_bom();
+    ///     response
+    /// };
+    ///
+    /// let response = csv("text/csv; charset=utf-8");
+    /// assert!(matches!(&response.body, ResponseBody::Buffered(body) if body[..] == *b"\xEF\xBB\xBFa,b,c\n"));
+    /// assert_eq!(response.headers["Content-Length"], "9");
+    ///
+    /// let response = csv("application/octet-stream");
+    /// assert!(matches!(&response.body, ResponseBody::Buffered(body) if body[..] == *b"a,b,c\n"));
+    /// assert_eq!(response.headers["Content-Length"], "6");
+    /// ```
+    pub fn with_bom(&mut self) -> &mut Self {
+        const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+        let is_text = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(is_text_content_type);
+        let ResponseBody::Buffered(body) = &self.body else {
+            return self;
+        };
+        if !is_text || body.starts_with(BOM) {
+            return self;
+        }
+
+        let mut with_bom = Vec::with_capacity(BOM.len() + body.len());
+        with_bom.extend_from_slice(BOM);
+        with_bom.extend_from_slice(body);
+        self.body = ResponseBody::Buffered(with_bom.into());
+
+        let content_length = self
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.trim().parse::<usize>().ok());
+        if let Some(content_length) = content_length {
+            self.headers.insert(
+                CONTENT_LENGTH,
+                HeaderValue::from(content_length + BOM.len()),
+            );
+        }
+        self
+    }
+
+    /// Appends `; charset=utf-8` to a text-like `Content-Type` (`text/*`, JSON, XML and JavaScript) which has no
+    /// `charset` parameter, so clients don't have to guess the encoding.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// let content_type = |content_type: &str| {
+    ///     let mut response = CGIResponse::builder().header("Content-Type", content_type).build().unwrap();
+    ///     response.with_utf8_charset();
+    ///     response.headers["Content-Type"].clone()
+    /// };
+    ///
+    /// assert_eq!(content_type("text/html"), "text/html; charset=utf-8");
+    /// assert_eq!(content_type("text/html; Charset=ISO-8859-1"), "text/html; Charset=ISO-8859-1");
+    /// assert_eq!(content_type("image/png"), "image/png");
+    /// ```
+    pub fn with_utf8_charset(&mut self) -> &mut Self {
+        let Some(content_type) = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return self;
+        };
+        let has_charset = content_type
+            .split(';')
+            .skip(1)
+            .filter_map(|param| param.split_once('='))
+            .any(|(name, _)| name.trim().eq_ignore_ascii_case("charset"));
+
+        if is_text_content_type(content_type) && !has_charset {
+            let value = format!("{}; charset=utf-8", content_type.trim_end());
+            if let Ok(value) = HeaderValue::try_from(value) {
+                self.headers.insert(CONTENT_TYPE, value);
+            }
+        }
+        self
+    }
+
+    /// Sets `Content-Disposition: attachment`, so the client downloads the body as a file named `filename` instead of
+    /// displaying it.
+    ///
+    /// A non-ASCII `filename` is sent percent-encoded in a `filename*=UTF-8''` parameter per RFC 6266, after a plain
+    /// `filename` fallback for older clients in which those characters are replaced with `_`.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// let disposition = |filename: &str| {
+    ///     let mut response = CGIResponse::new(StatusCode::OK);
+    ///     response.attachment(filename);
+    ///     response.headers["Content-Disposition"].clone()
+    /// };
+    ///
+    /// assert_eq!(disposition("report.csv"), "attachment; filename=\"report.csv\"");
+    /// assert_eq!(disposition("a \"quoted\" name"), r#"attachment; filename="a \"quoted\" name""#);
+    /// assert_eq!(
+    ///     disposition("Übersicht 2024.pdf"),
+    ///     "attachment; filename=\"_bersicht 2024.pdf\"; filename*=UTF-8''%C3%9Cbersicht%202024.pdf"
+    /// );
+    /// ```
+    pub fn attachment(&mut self, filename: &str) -> &mut Self {
+        let mut value = String::from("attachment; filename=\"");
+        for c in filename.chars() {
+            match c {
+                '"' | '\\' => {
+                    value.push('\\');
+                    value.push(c);
+                }
+                ' ' | '!'..='~' => value.push(c),
+                _ => value.push('_'),
+            }
+        }
+        value.push('"');
+
+        if !filename.bytes().all(|b| b == b' ' || b.is_ascii_graphic()) {
+            value.push_str("; filename*=UTF-8''");
+            for b in filename.bytes() {
+                match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => value.push(b as char),
+                    b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|'
+                    | b'~' => value.push(b as char),
+                    _ => value.push_str(&format!("%{:02X}", b)),
+                }
+            }
+        }
+
+        // Only visible ASCII and spaces were written, so the value is always valid.
+        if let Ok(value) = HeaderValue::try_from(value) {
+            self.headers.insert(CONTENT_DISPOSITION, value);
+        }
+        self
+    }
+
+    /// Creates a response from `response`, collecting its body into memory.
+    ///
+    /// Fails with [`CGIError::ReadResponseBody`](crate::CGIError::ReadResponseBody) if the body produces an error.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use http_body_util::Full;
+    /// use hyper::{body::Bytes, Response, StatusCode};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let response = Response::builder()
+    ///     .status(StatusCode::NOT_FOUND)
+    ///     .body(Full::new(Bytes::from("missing")))
+    ///     .unwrap();
+    ///
+    /// let response = CGIResponse::from_hyper(response).await.unwrap();
+    /// assert_eq!(response.status, "404");
+    /// assert_eq!(response.reason.as_deref(), Some("Not Found"));
+    /// assert_eq!(response.body.to_bytes().await.unwrap(), "missing");
+    /// # }
+    /// ```
+    pub async fn from_hyper<B>(response: Response<B>) -> Result<CGIResponse>
+    where
+        B: Body,
+        B::Error: Into<BoxError>,
+    {
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(Into::into)
+            .context(error::ReadResponseBodySnafu)?
+            .to_bytes();
+
+        let mut cgi_response = Self::new(parts.status);
+        cgi_response.headers = parts.headers;
+        cgi_response.body = body.into();
+        Ok(cgi_response)
+    }
+
+    /// Creates a response from `response`, keeping its body as a stream to be polled while the response is written.
+    ///
+    /// Trailers can't be represented in a CGI response; any trailer frames are dropped when the body is written.
+    ///
+    /// ```rust
+    /// use cgi_rs::{body::ReaderBody, CGIResponse};
+    /// use hyper::Response;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let response = Response::builder()
+    ///     .header("Content-Type", "text/plain")
+    ///     .body(ReaderBody::new(&b"streamed body"[..], 13))
+    ///     .unwrap();
+    ///
+    /// let mut output = Vec::new();
+    /// CGIResponse::from_streaming(response)
+    ///     .write_streaming_response_to_output(&mut output)
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(output, b"Status: 200 OK\r\ncontent-type: text/plain\r\n\r\nstreamed body");
+    /// # }
+    /// ```
+    pub fn from_streaming<B>(response: Response<B>) -> CGIResponse
+    where
+        B: Body + Send + 'static,
+        B::Error: Into<BoxError>,
+    {
+        let (parts, body) = response.into_parts();
+        let body = body
+            .map_frame(|frame| frame.map_data(|mut data| data.copy_to_bytes(data.remaining())))
+            .map_err(Into::into)
+            .boxed_unsync();
+
+        let mut cgi_response = Self::new(parts.status);
+        cgi_response.headers = parts.headers;
+        cgi_response.body = ResponseBody::Stream(body);
+        cgi_response
+    }
+
+    /// Creates a `200 OK` response whose body streams `content_length` bytes from `reader`, such as a
+    /// [`tokio::fs::File`](https://docs.rs/tokio/latest/tokio/fs/struct.File.html), so it is never held in memory.
+    ///
+    /// `Content-Length` is set to `content_length`. If reading fails or ends early, the body is truncated, since the
+    /// headers have already been sent, and writing it fails with
+    /// [`CGIError::ReadResponseBody`](crate::CGIError::ReadResponseBody).
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let file = &b"file contents"[..];
+    ///
+    /// let mut output = Vec::new();
+    /// CGIResponse::from_async_read(file, 13)
+    ///     .write_streaming_response_to_output(&mut output)
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(output, b"Status: 200 OK\r\ncontent-length: 13\r\n\r\nfile contents");
+    ///
+    /// // A reader ending early truncates the body.
+    /// let mut output = Vec::new();
+    /// let result = CGIResponse::from_async_read(file, 20)
+    ///     .write_streaming_response_to_output(&mut output)
+    ///     .await;
+    /// assert!(matches!(result, Err(CGIError::ReadResponseBody { .. })));
+    /// assert!(output.ends_with(b"\r\n\r\nfile contents"));
+    ///
+    /// // Writing trailers frames the body as chunked, which replaces the `Content-Length`.
+    /// let mut response = CGIResponse::from_async_read(file, 13);
+    /// response.options.nph = true;
+    /// response.options.trailers = true;
+    /// let mut output = Vec::new();
+    /// response.write_streaming_response_to_output(&mut output).await.unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\nd\r\nfile contents\r\n0\r\n\r\n"
+    /// );
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn from_async_read(
+        reader: impl tokio::io::AsyncRead + Send + Unpin + 'static,
+        content_length: usize,
+    ) -> CGIResponse {
+        let body = crate::body::AsyncReaderBody::new(reader, content_length)
+            .map_err(Into::into)
+            .boxed_unsync();
+
+        let mut response = Self::new(StatusCode::OK);
+        response
+            .headers
+            .insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+        response.body = ResponseBody::Stream(body);
+        response
+    }
+
+    /// Creates a `200 OK` response whose body streams the contents of each file in `paths`, one after another, such
+    /// as the parts of a bundled script.
+    ///
+    /// Every file is opened before the response is created, so a missing file fails with
+    /// [`CGIError::OpenResponseFile`](crate::CGIError::OpenResponseFile) before anything is written. `Content-Length`
+    /// is the sum of the file sizes, and each file contributes at most the size it had when opened.
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let dir = std::env::temp_dir();
+    /// let (first, second) = (dir.join("cgi-rs-bundle-a.js"), dir.join("cgi-rs-bundle-b.js"));
+    /// std::fs::write(&first, "let a = 1;\n").unwrap();
+    /// std::fs::write(&second, "let b = 2;\n").unwrap();
+    ///
+    /// let mut output = Vec::new();
+    /// CGIResponse::from_files([&first, &second])
+    ///     .await
+    ///     .unwrap()
+    ///     .write_streaming_response_to_output(&mut output)
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(output, b"Status: 200 OK\r\ncontent-length: 22\r\n\r\nlet a = 1;\nlet b = 2;\n");
+    ///
+    /// let result = CGIResponse::from_files([first, dir.join("cgi-rs-bundle-missing.js")]).await;
+    /// assert!(matches!(result, Err(CGIError::OpenResponseFile { .. })));
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn from_files(
+        paths: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
+    ) -> Result<CGIResponse> {
+        use tokio::io::AsyncReadExt;
+
+        let mut reader: Box<dyn tokio::io::AsyncRead + Send + Unpin> = Box::new(tokio::io::empty());
+        let mut content_length = 0;
+        for path in paths {
+            let path = path.as_ref();
+            let context = || error::OpenResponseFileSnafu { path };
+            let file = tokio::fs::File::open(path)
+                .await
+                .with_context(|_| context())?;
+            let len = file.metadata().await.with_context(|_| context())?.len();
+            content_length += len as usize;
+            reader = Box::new(reader.chain(file.take(len)));
+        }
+
+        Ok(Self::from_async_read(reader, content_length))
+    }
+
+    /// Writes the response to `output` in CGI format.
+    ///
+    /// If [`CGIResponse::reason`] is `None`, the canonical reason phrase for the status is written, if it has one.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::StatusCode;
+    ///
+    /// let write_status = |status: &str| {
+    ///     let mut response = CGIResponse::new(StatusCode::OK);
+    ///     response.status = status.to_string();
+    ///     response.reason = None;
+    ///     let mut output = Vec::new();
+    ///     response.write_response_to_output(&mut output).unwrap();
+    ///     String::from_utf8(output).unwrap().lines().next().unwrap().to_string()
+    /// };
+    ///
+    /// assert_eq!(write_status("404"), "Status: 404 Not Found");
+    /// assert_eq!(write_status("599"), "Status: 599");
+    /// ```
+    ///
+    /// A `200` response with a `Location` header is written without a `Status` line, as a client redirect response,
+    /// so the server performs the redirect. If the location is a path and the body is empty, it is a local redirect
+    /// (RFC 3875 §6.2.2), which the server re-processes internally: only the `Location` header is written.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::{header::CONTENT_TYPE, http::HeaderValue, StatusCode};
+    ///
+    /// let mut response = CGIResponse::redirect(HeaderValue::from_static("/other"), StatusCode::OK);
+    /// response.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(output, b"location: /other\r\n\r\n");
+    ///
+    /// let mut response = CGIResponse::redirect(HeaderValue::from_static("https://example.com/"), StatusCode::OK);
+    /// response.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"location: https://example.com/\r\ncontent-type: text/html\r\ncontent-length: 0\r\n\r\n"
+    /// );
+    /// ```
+    ///
+    /// Every value of a multi-valued header, such as `Set-Cookie`, is written on its own line, in order.
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::{header::SET_COOKIE, http::HeaderValue};
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// response.headers.append(SET_COOKIE, HeaderValue::from_static("session=abc; HttpOnly"));
+    /// response.headers.append(SET_COOKIE, HeaderValue::from_static("theme=dark"));
+    ///
+    /// let mut output = Vec::new();
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// let output = String::from_utf8(output).unwrap();
+    /// let cookies: Vec<_> = output.lines().filter(|line| line.starts_with("set-cookie:")).collect();
+    /// assert_eq!(cookies, ["set-cookie: session=abc; HttpOnly", "set-cookie: theme=dark"]);
+    /// ```
+    ///
+    /// Output is buffered, so the status line and headers are written together rather than one line at a time, and
+    /// `output` is flushed before returning.
+    ///
+    /// ```rust
+    /// use std::io::{self, Write};
+    /// use cgi_rs::CGIResponse;
+    /// use hyper::http::{HeaderName, HeaderValue};
+    ///
+    /// // Counts the writes reaching the underlying output.
+    /// struct Counting(usize);
+    ///
+    /// impl Write for Counting {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         self.0 += 1;
+    ///         Ok(buf.len())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// for i in 0..20 {
+    ///     let name = HeaderName::try_from(format!("x-header-{}", i)).unwrap();
+    ///     response.headers.insert(name, HeaderValue::from_static("value"));
+    /// }
+    ///
+    /// let mut output = Counting(0);
+    /// response.write_response_to_output(&mut output).unwrap();
+    /// assert_eq!(output.0, 1);
+    /// ```
+    ///
+    /// Nothing is written if a header value or the status contains a line break, which could split the response;
+    /// this fails with [`CGIError::InvalidHeaderValue`](crate::CGIError::InvalidHeaderValue) instead.
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    ///
+    /// let mut response = CGIResponse::uri_too_long();
+    /// response.reason = Some(String::from("URI Too Long\r\nSet-Cookie: evil=1"));
+    ///
+    /// let mut output = Vec::new();
+    /// let result = response.write_response_to_output(&mut output);
+    /// assert!(matches!(result, Err(CGIError::InvalidHeaderValue { name }) if name == "Status"));
+    /// assert!(output.is_empty());
+    /// ```
+    ///
+    /// A buffered body gets a `Content-Length` header unless one was already set, the response uses
+    /// `Transfer-Encoding`, or the status (`1xx`, `204`, `304`) can't carry a body.
+    ///
+    /// Short writes are retried until everything has been written, so a pipe accepting partial writes still receives
+    /// the complete response.
+    ///
+    /// ```rust
+    /// use std::io::{self, Write};
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// // A writer that accepts at most 4 bytes per call.
+    /// struct Trickle(Vec<u8>);
+    ///
+    /// impl Write for Trickle {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         let n = buf.len().min(4);
+    ///         self.0.extend_from_slice(&buf[..n]);
+    ///         Ok(n)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let mut output = Trickle(Vec::new());
+    /// CGIResponse::uri_too_long()
+    ///     .write_response_to_output(&mut output)
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     output.0,
+    ///     b"Status: 414 URI Too Long\r\ncontent-type: text/plain\r\ncontent-length: 12\r\n\r\nURI Too Long"
+    /// );
+    /// ```
+    pub fn write_response_to_output(mut self, output: impl Write) -> Result<()> {
+        if let ResponseBody::Stream(_) = self.body {
+            return error::StreamingBodySnafu.fail();
+        }
+        self.prepare()?;
+
+        // Collect the status line and headers into as few writes as possible.
+        let mut output = BufWriter::new(output);
+        self.write_status(&mut output)?;
+        self.write_headers(&mut output)?;
+        if let ResponseBody::Buffered(body) = &self.body {
+            output.write_all(body).context(error::WriteResponseSnafu)?;
+        }
+        output.flush().context(error::WriteResponseSnafu)?;
 
         Ok(())
     }
 
-    async fn write_status(&self, output: &mut impl Write) -> Result<()> {
-        // If a canonical reason is present, write it in the status line.
-        if let Some(reason) = &self.reason {
-            output
-                .write(format!("Status: {} {}\n", self.status, reason).as_bytes())
-                .context(error::WriteResponseSnafu)?;
+    /// Writes the response to `output` like [`CGIResponse::write_response_to_output`], awaiting each frame of a
+    /// streamed body such as one created by [`CGIResponse::from_streaming`].
+    ///
+    /// `output` is flushed after the headers and after each frame, so the client receives data as it is produced.
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    /// use http_body_util::Full;
+    /// use hyper::{body::Bytes, Response};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let streaming = || CGIResponse::from_streaming(Response::new(Full::new(Bytes::from("streamed"))));
+    ///
+    /// let result = streaming().write_response_to_output(Vec::new());
+    /// assert!(matches!(result, Err(CGIError::StreamingBody)));
+    ///
+    /// let mut output = Vec::new();
+    /// streaming().write_streaming_response_to_output(&mut output).await.unwrap();
+    /// assert_eq!(output, b"Status: 200 OK\r\n\r\nstreamed");
+    /// # }
+    /// ```
+    pub async fn write_streaming_response_to_output(mut self, output: impl Write) -> Result<()> {
+        self.prepare()?;
+        let chunked = self.writes_trailers();
+
+        let mut output = BufWriter::new(output);
+        self.write_status(&mut output)?;
+        self.write_headers(&mut output)?;
+        match self.body {
+            ResponseBody::Buffered(body) => {
+                output.write_all(&body).context(error::WriteResponseSnafu)?;
+            }
+            ResponseBody::Stream(mut body) => {
+                // Send the headers before waiting for the first frame.
+                output.flush().context(error::WriteResponseSnafu)?;
+                let mut trailers = HeaderMap::new();
+                while let Some(frame) = body.frame().await {
+                    let frame = frame.context(error::ReadResponseBodySnafu)?;
+                    match frame.into_data() {
+                        Ok(data) if chunked => {
+                            output
+                                .write_all(&self.options.chunk(&data))
+                                .context(error::WriteResponseSnafu)?;
+                        }
+                        Ok(data) => output.write_all(&data).context(error::WriteResponseSnafu)?,
+                        Err(frame) => trailers.extend(frame.into_trailers().unwrap_or_default()),
+                    }
+                    output.flush().context(error::WriteResponseSnafu)?;
+                }
+                if chunked {
+                    output
+                        .write_all(&self.options.last_chunk(&trailers)?)
+                        .context(error::WriteResponseSnafu)?;
+                }
+            }
+        }
+        output.flush().context(error::WriteResponseSnafu)?;
+
+        Ok(())
+    }
+
+    /// Writes the response to `output` like [`CGIResponse::write_response_to_output`], awaiting each write so the
+    /// async runtime isn't blocked, e.g. on [`tokio::io::stdout`].
+    ///
+    /// ```rust
+    /// use cgi_rs::CGIResponse;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let mut output = Vec::new();
+    /// CGIResponse::uri_too_long()
+    ///     .write_response_to_output_async(&mut output)
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     output,
+    ///     b"Status: 414 URI Too Long\r\ncontent-type: text/plain\r\ncontent-length: 12\r\n\r\nURI Too Long"
+    /// );
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn write_response_to_output_async(
+        mut self,
+        mut output: impl tokio::io::AsyncWrite + Unpin,
+    ) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        self.prepare()?;
+        let chunked = self.writes_trailers();
+
+        let mut head = Vec::new();
+        self.write_status(&mut head)?;
+        self.write_headers(&mut head)?;
+        output
+            .write_all(&head)
+            .await
+            .context(error::WriteResponseSnafu)?;
+
+        match self.body {
+            ResponseBody::Buffered(body) => {
+                output
+                    .write_all(&body)
+                    .await
+                    .context(error::WriteResponseSnafu)?;
+            }
+            ResponseBody::Stream(mut body) => {
+                output.flush().await.context(error::WriteResponseSnafu)?;
+                let mut trailers = HeaderMap::new();
+                while let Some(frame) = body.frame().await {
+                    let frame = frame.context(error::ReadResponseBodySnafu)?;
+                    match frame.into_data() {
+                        Ok(data) if chunked => {
+                            let chunk = self.options.chunk(&data);
+                            output
+                                .write_all(&chunk)
+                                .await
+                                .context(error::WriteResponseSnafu)?;
+                        }
+                        Ok(data) => output
+                            .write_all(&data)
+                            .await
+                            .context(error::WriteResponseSnafu)?,
+                        Err(frame) => trailers.extend(frame.into_trailers().unwrap_or_default()),
+                    }
+                    output.flush().await.context(error::WriteResponseSnafu)?;
+                }
+                if chunked {
+                    let last_chunk = self.options.last_chunk(&trailers)?;
+                    output
+                        .write_all(&last_chunk)
+                        .await
+                        .context(error::WriteResponseSnafu)?;
+                }
+            }
+        }
+        output.flush().await.context(error::WriteResponseSnafu)?;
+
+        Ok(())
+    }
+
+    // Completes and validates the headers, before anything is written.
+    fn prepare(&mut self) -> Result<()> {
+        if self.status.is_empty() {
+            self.status = StatusCode::OK.as_str().to_string();
+        }
+        self.insert_content_length();
+        if self.writes_trailers() {
+            // A message framed both ways could be read differently by the client and intermediaries (RFC 9112 §6.3).
+            self.headers.remove(CONTENT_LENGTH);
+            self.headers
+                .insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+        }
+        self.validate()
+    }
+
+    // Trailers can only reach the client through the chunked framing of an NPH response.
+    fn writes_trailers(&self) -> bool {
+        self.options.nph && self.options.trailers && matches!(self.body, ResponseBody::Stream(_))
+    }
+
+    /// Checks that the response can be written as a well-formed CGI header block, as the write methods do before
+    /// writing anything.
+    ///
+    /// Every header name must be an HTTP token (RFC 7230 §3.2.6), no header value or status field may contain a line
+    /// break, and the header block must fit in [`WriteOptions::max_header_block_size`].
+    ///
+    /// ```rust
+    /// use cgi_rs::{CGIError, CGIResponse};
+    /// use hyper::StatusCode;
+    ///
+    /// let mut response = CGIResponse::new(StatusCode::OK);
+    /// assert!(response.validate().is_ok());
+    ///
+    /// response.reason = Some("OK\r\nX-Injected: 1".to_string());
+    /// assert!(matches!(response.validate(), Err(CGIError::InvalidHeaderValue { .. })));
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        self.check_header_names()?;
+        self.check_header_values()?;
+        self.check_header_block_size()
+    }
+
+    fn insert_content_length(&mut self) {
+        let ResponseBody::Buffered(body) = &self.body else {
+            return;
+        };
+        let bodiless_status = self
+            .status
+            .parse::<u16>()
+            .is_ok_and(|status| status < 200 || status == 204 || status == 304);
+
+        if !bodiless_status
+            && !self.headers.contains_key(CONTENT_LENGTH)
+            && !self.headers.contains_key(TRANSFER_ENCODING)
+        {
+            self.headers
+                .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        }
+    }
+
+    // `HeaderName` enforces the token rules when parsing, but the names are checked again in the form they're
+    // written, after `header_case` is applied.
+    fn check_header_names(&self) -> Result<()> {
+        for name in self.headers.keys() {
+            let written = self.options.header_case.apply(name.as_str());
+            ensure!(
+                !written.is_empty() && written.bytes().all(is_token_char),
+                error::InvalidHeaderNameSnafu {
+                    name: written.as_str()
+                }
+            );
+        }
+        Ok(())
+    }
+
+    // `HeaderValue`s built without validation (e.g. in release builds) and the free-form status fields may contain line
+    // breaks, which would let them inject headers.
+    fn check_header_values(&self) -> Result<()> {
+        let has_line_break = |value: &[u8]| value.iter().any(|&b| b == b'\r' || b == b'\n');
+
+        let status = [
+            self.status.as_str(),
+            self.reason.as_deref().unwrap_or_default(),
+        ];
+        if status.iter().any(|value| has_line_break(value.as_bytes())) {
+            return error::InvalidHeaderValueSnafu { name: "Status" }.fail();
+        }
+        match self
+            .headers
+            .iter()
+            .find(|(_, value)| has_line_break(value.as_bytes()))
+        {
+            Some((name, _)) => error::InvalidHeaderValueSnafu {
+                name: name.as_str(),
+            }
+            .fail(),
+            None => Ok(()),
+        }
+    }
+
+    fn check_header_block_size(&self) -> Result<()> {
+        let line_ending = self.options.line_ending.as_str();
+        let headers_size: usize = self
+            .header_fields()
+            .map(|(key, value)| key.as_str().len() + ": ".len() + value.len() + line_ending.len())
+            .sum();
+        let size = self.status_line().len() + headers_size + line_ending.len();
+        let limit = self.options.max_header_block_size;
+
+        ensure!(
+            size <= limit,
+            error::HeaderBlockTooLargeSnafu { size, limit }
+        );
+        Ok(())
+    }
+
+    // A local redirect (RFC 3875 §6.2.2) consists of nothing but a `Location` holding an absolute path.
+    fn is_local_redirect(&self) -> bool {
+        if self.options.nph {
+            return false;
+        }
+
+        let is_path = |location: &HeaderValue| {
+            let location = location.as_bytes();
+            location.starts_with(b"/") && !location.starts_with(b"//")
+        };
+
+        self.status == "200"
+            && self.headers.get(LOCATION).is_some_and(is_path)
+            && matches!(&self.body, ResponseBody::Buffered(body) if body.is_empty())
+    }
+
+    // The header fields to write, which for a local redirect is only the `Location`.
+    fn header_fields(&self) -> impl Iterator<Item = (&HeaderName, &HeaderValue)> {
+        let local_redirect = self.is_local_redirect();
+        self.headers
+            .iter()
+            .filter(move |(key, _)| !local_redirect || *key == LOCATION)
+    }
+
+    fn status_line(&self) -> String {
+        let line_ending = self.options.line_ending.as_str();
+        // Without an explicit reason, fall back to the canonical one for the status, if it is a known code.
+        let reason = self.reason.as_deref().or_else(|| {
+            self.status
+                .parse::<StatusCode>()
+                .ok()
+                .and_then(|status| status.canonical_reason())
+        });
+
+        if self.options.nph {
+            let reason = reason.unwrap_or_default();
+            return format!(
+                "{} {} {}{}",
+                self.options.server_protocol, self.status, reason, line_ending
+            );
+        }
+
+        // A `Location` without an explicit status is a redirect response (RFC 3875 §6.2.2 and §6.2.3), which servers
+        // would turn into a `200` if a `Status: 200` were written. Leave the status to the server instead.
+        if self.status == "200" && self.headers.contains_key(LOCATION) {
+            return String::new();
+        }
+
+        if let Some(reason) = reason {
+            format!("Status: {} {}{}", self.status, reason, line_ending)
         } else {
-            output
-                .write(format!("Status: {}\n", self.status).as_bytes())
-                .context(error::WriteResponseSnafu)?;
+            format!("Status: {}{}", self.status, line_ending)
         }
+    }
+
+    fn write_status(&self, output: &mut impl Write) -> Result<()> {
+        output
+            .write_all(self.status_line().as_bytes())
+            .context(error::WriteResponseSnafu)?;
         Ok(())
     }
 
-    async fn write_headers(&self, output: &mut impl Write) -> Result<()> {
-        for (key, value) in self.headers.iter() {
-            let mut header_bytes = format!("{}: ", key).into_bytes();
+    fn write_headers(&self, output: &mut impl Write) -> Result<()> {
+        for (key, value) in self.header_fields() {
+            let mut header_bytes =
+                format!("{}: ", self.options.header_case.apply(key.as_str())).into_bytes();
             header_bytes.extend(value.as_bytes());
-            header_bytes.extend(b"\n");
+            header_bytes.extend(self.options.line_ending.as_str().as_bytes());
             output
-                .write(&header_bytes)
+                .write_all(&header_bytes)
                 .context(error::WriteResponseSnafu)?;
         }
 
-        output.write(b"\n").context(error::WriteResponseSnafu)?;
+        output
+            .write_all(self.options.line_ending.as_str().as_bytes())
+            .context(error::WriteResponseSnafu)?;
 
         Ok(())
     }
+}
 
-    async fn write_body(self, output: &mut impl Write) -> Result<()> {
-        let body = self.body;
+/// A builder for a [`CGIResponse`], created by [`CGIResponse::builder`].
+///
+/// Invalid statuses and headers are reported by [`CGIResponseBuilder::build`].
+#[derive(Debug)]
+pub struct CGIResponseBuilder {
+    inner: Result<CGIResponse>,
+}
 
-        output.write(body.as_ref()).context(error::WriteResponseSnafu)?;
+impl CGIResponseBuilder {
+    /// Sets the status code, along with its canonical reason phrase.
+    pub fn status(self, status: u16) -> Self {
+        self.and_then(|response| {
+            let status = StatusCode::from_u16(status)
+                .map_err(hyper::http::Error::from)
+                .context(error::InvalidResponseSnafu)?;
+            let status = CGIResponse::new(status);
+            Ok(CGIResponse {
+                status: status.status,
+                reason: status.reason,
+                ..response
+            })
+        })
+    }
 
-        Ok(())
+    /// Appends a header, keeping any previous values with the same name.
+    ///
+    /// A name that isn't an HTTP token, such as `Content Type`, is reported by [`CGIResponseBuilder::build`] as
+    /// [`CGIError::InvalidHeaderName`](crate::CGIError::InvalidHeaderName).
+    pub fn header<K, V>(self, name: K, value: V) -> Self
+    where
+        K: AsRef<str>,
+        V: TryInto<HeaderValue>,
+        V::Error: Into<hyper::http::Error>,
+    {
+        self.and_then(|mut response| {
+            let name = name.as_ref();
+            let name = HeaderName::try_from(name)
+                .ok()
+                .context(error::InvalidHeaderNameSnafu { name })?;
+            let value = value
+                .try_into()
+                .map_err(Into::into)
+                .context(error::InvalidResponseSnafu)?;
+            response.headers.append(name, value);
+            Ok(response)
+        })
+    }
+
+    /// Sets the body.
+    pub fn body(self, body: impl Into<Bytes>) -> Self {
+        self.and_then(|response| {
+            Ok(CGIResponse {
+                body: ResponseBody::Buffered(body.into()),
+                ..response
+            })
+        })
+    }
+
+    /// Returns the response, or the first error caused by an invalid status or header.
+    pub fn build(self) -> Result<CGIResponse> {
+        self.inner
+    }
+
+    fn and_then(self, f: impl FnOnce(CGIResponse) -> Result<CGIResponse>) -> Self {
+        CGIResponseBuilder {
+            inner: self.inner.and_then(f),
+        }
     }
 }
 
+// tchar from RFC 7230 §3.2.6.
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
@@ -1,20 +1,101 @@
 use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::path::Path;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+
+// How long an idle keep-alive connection is held open waiting for the next request.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// The largest request line and headers accepted, larger requests are answered with 431.
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+const USAGE: &str =
+    "Usage: http-cgi-server [--address <ip>] [--port <port>] [--cgi-bin <directory>]";
+
+/// Where the server listens and which scripts it runs, set from the command line.
+struct Config {
+    address: IpAddr,
+    port: u16,
+    cgi_bin: PathBuf,
+}
+
+impl Config {
+    /// Parses the command-line arguments (without the program name), defaulting to `127.0.0.1`, port `8080` and
+    /// `./cgi-bin`.
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        let mut config = Config {
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8080,
+            cgi_bin: PathBuf::from("cgi-bin"),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--address" => {
+                    let address = value()?;
+                    config.address = address.parse().map_err(|_| {
+                        format!("Invalid bind address {:?}, expected an IP address", address)
+                    })?;
+                }
+                "--port" => {
+                    let port = value()?;
+                    config.port = port.parse().map_err(|_| {
+                        format!("Invalid port {:?}, expected a number up to 65535", port)
+                    })?;
+                }
+                "--cgi-bin" => config.cgi_bin = PathBuf::from(value()?),
+                _ => return Err(format!("Unknown argument {:?}", arg)),
+            }
+        }
+
+        // Scripts are resolved against cgi-bin from each connection's thread, so fix it relative to the starting
+        // directory.
+        if config.cgi_bin.is_relative() {
+            let current_dir = std::env::current_dir()
+                .map_err(|e| format!("Failed to read current directory: {}", e))?;
+            config.cgi_bin = current_dir.join(&config.cgi_bin);
+        }
+        Ok(config)
+    }
+}
 
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
-    println!("CGI HTTP Server listening on http://127.0.0.1:8080");
-    println!("CGI scripts should be placed in ./cgi-bin/");
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let bind_address = SocketAddr::new(config.address, config.port);
+    let listener = match TcpListener::bind(bind_address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", bind_address, e);
+            std::process::exit(1);
+        }
+    };
+    println!("CGI HTTP Server listening on http://{}", bind_address);
+    println!(
+        "CGI scripts should be placed in {}",
+        config.cgi_bin.display()
+    );
 
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
-                thread::spawn(|| {
-                    handle_connection(stream);
+                let config = config.clone();
+                thread::spawn(move || {
+                    handle_connection(stream, &config);
                 });
             }
             Err(e) => {
@@ -24,22 +105,45 @@ fn main() {
     }
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let mut buffer = [0; 1024];
-    let n = stream.read(&mut buffer).unwrap();
-    let request = String::from_utf8_lossy(&buffer[..n]);
+fn handle_connection(mut stream: TcpStream, config: &Config) {
+    if let Err(e) = stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT)) {
+        eprintln!("Error setting read timeout: {}", e);
+        return;
+    }
+
+    // Keep serving requests on this connection until the client no longer wants it kept alive. Bytes read past the end
+    // of one request are the start of the next, which a client may send without waiting for the response.
+    let mut pending = Vec::new();
+    while handle_request(&mut stream, config, &mut pending) {}
+}
+
+/// Handles a single request, starting with the bytes `pending` from the previous one, returning whether the connection
+/// should be kept open for another. Any bytes read past the end of the request are left in `pending`.
+fn handle_request(stream: &mut TcpStream, config: &Config, pending: &mut Vec<u8>) -> bool {
+    let (mut buffer, header_end) = match read_head(stream, std::mem::take(pending)) {
+        Ok(Some(head)) => head,
+        Ok(None) => return false,
+        Err(e) if e.kind() == ErrorKind::InvalidData => {
+            let _ = send_error(stream, "431 Request Header Fields Too Large", false);
+            return false;
+        }
+        Err(_) => return false,
+    };
+    let already_read = buffer.split_off(header_end);
+    let request = String::from_utf8_lossy(&buffer);
 
     let mut lines = request.lines();
     let first_line = lines.next().unwrap_or("");
     let parts: Vec<&str> = first_line.split_whitespace().collect();
 
     if parts.len() < 3 {
-        send_error(stream, "400 Bad Request");
-        return;
+        let _ = send_error(stream, "400 Bad Request", false);
+        return false;
     }
 
     let method = parts[0];
     let path = parts[1];
+    let version = parts[2];
 
     // Parse headers
     let mut headers = HashMap::new();
@@ -52,32 +156,145 @@ fn handle_connection(mut stream: TcpStream) {
         }
     }
 
+    let keep_alive = wants_keep_alive(version, &headers);
+
+    let content_length = match headers
+        .get("content-length")
+        .map(|value| value.trim().parse::<usize>())
+    {
+        None => 0,
+        Some(Ok(content_length)) => content_length,
+        Some(Err(_)) => {
+            let _ = send_error(stream, "400 Bad Request", false);
+            return false;
+        }
+    };
+    let body = match read_body(stream, already_read, content_length) {
+        Ok((body, leftover)) => {
+            *pending = leftover;
+            body
+        }
+        Err(e) => {
+            eprintln!("Error reading request body: {}", e);
+            return false;
+        }
+    };
+
     // Check if this is a CGI request
-    if path.starts_with("/cgi-bin/") {
-        handle_cgi_request(stream, method, path, headers);
+    let written = if path.starts_with("/cgi-bin/") {
+        handle_cgi_request(stream, config, method, path, headers, &body, keep_alive)
+    } else {
+        send_error(stream, "404 Not Found", keep_alive)
+    };
+
+    // A client which went away mid-response can't receive another.
+    if let Err(e) = written {
+        eprintln!("Error writing response: {}", e);
+        return false;
+    }
+    keep_alive
+}
+
+/// Reads from `stream` until the blank line ending the request headers, starting with the bytes `buffer` already
+/// read, returning everything read and the offset of the body within it, since the last read may include the start of
+/// the body.
+///
+/// Returns `Ok(None)` if the connection closes first, and an [`ErrorKind::InvalidData`] error if the headers exceed
+/// [`MAX_HEAD_SIZE`].
+fn read_head(
+    stream: &mut impl Read,
+    mut buffer: Vec<u8>,
+) -> std::io::Result<Option<(Vec<u8>, usize)>> {
+    let mut chunk = [0; 1024];
+    let mut search_from = 0;
+    loop {
+        let terminator = buffer[search_from..]
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n");
+        let header_end = terminator.map(|position| search_from + position + 4);
+        if header_end.unwrap_or(buffer.len()) > MAX_HEAD_SIZE {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        if let Some(header_end) = header_end {
+            return Ok(Some((buffer, header_end)));
+        }
+
+        // The terminator may straddle the next read.
+        search_from = buffer.len().saturating_sub(3);
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Reads a request body of `content_length` bytes, starting with the bytes `already_read` along with the headers.
+/// Returns the body and any bytes read past it.
+fn read_body(
+    stream: &mut impl Read,
+    mut already_read: Vec<u8>,
+    content_length: usize,
+) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+    let leftover = already_read.split_off(already_read.len().min(content_length));
+    let mut body = already_read;
+    let remaining = content_length - body.len();
+    stream.take(remaining as u64).read_to_end(&mut body)?;
+
+    if body.len() < content_length {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+    Ok((body, leftover))
+}
+
+/// Returns whether the client wants the connection kept open after this request.
+///
+/// HTTP/1.1 connections are persistent unless the client sends `Connection: close`, while HTTP/1.0 clients must opt
+/// in with `Connection: keep-alive`.
+fn wants_keep_alive(version: &str, headers: &HashMap<String, String>) -> bool {
+    let connection = headers
+        .get("connection")
+        .map(|value| value.to_ascii_lowercase())
+        .unwrap_or_default();
+    let has_token = |token: &str| connection.split(',').any(|t| t.trim() == token);
+
+    if has_token("close") {
+        false
+    } else if has_token("keep-alive") {
+        true
     } else {
-        send_error(stream, "404 Not Found");
+        version == "HTTP/1.1"
+    }
+}
+
+fn connection_header(keep_alive: bool) -> &'static str {
+    if keep_alive {
+        "Connection: keep-alive\r\n"
+    } else {
+        "Connection: close\r\n"
     }
 }
 
 fn handle_cgi_request(
-    mut stream: TcpStream,
+    stream: &mut TcpStream,
+    config: &Config,
     method: &str,
     path: &str,
     headers: HashMap<String, String>,
-) {
-    let script_name = path.trim_start_matches("/cgi-bin/");
-    let script_path = format!("./cgi-bin/{}", script_name);
-
-    let mut absolute_script_path = std::env::current_dir().unwrap();
-    absolute_script_path.push(&script_path);
-    absolute_script_path = absolute_script_path.canonicalize().unwrap();
-
-    // Check if script exists
-    if !absolute_script_path.exists() {
-        send_error(stream, "404 Not Found");
-        return;
-    }
+    body: &[u8],
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    // The query string isn't part of the script's path.
+    let (script_uri, query_string) = path.split_once('?').unwrap_or((path, ""));
+    // Decode first, so an encoded `..` can't slip past the containment check.
+    let Some(script_uri) = percent_decode(script_uri) else {
+        return send_error(stream, "400 Bad Request", keep_alive);
+    };
+    let (script_name, path_info, absolute_script_path) =
+        match resolve_script(&config.cgi_bin, script_uri.trim_start_matches("/cgi-bin/")) {
+            Ok(script) => script,
+            Err(status) => return send_error(stream, status, keep_alive),
+        };
 
     // Set up CGI environment variables
     let mut env_vars = HashMap::new();
@@ -93,7 +310,7 @@ fn handle_cgi_request(
     env_vars.insert("REQUEST_METHOD".to_string(), method.to_string());
     env_vars.insert("CONTENT_LENGTH".to_string(), content_length);
     env_vars.insert("REQUEST_URI".to_string(), path.to_string());
-    env_vars.insert("QUERY_STRING".to_string(), "".to_string());
+    env_vars.insert("QUERY_STRING".to_string(), query_string.to_string());
     env_vars.insert("CONTENT_TYPE".to_string(), content_type);
     env_vars.insert("SERVER_PROTOCOL".to_string(), "HTTP/1.1".to_string());
     env_vars.insert("GATEWAY_INTERFACE".to_string(), "CGI/1.1".to_string());
@@ -101,15 +318,23 @@ fn handle_cgi_request(
         "SERVER_SOFTWARE".to_string(),
         "http-cgi-server/1.0".to_string(),
     );
-    env_vars.insert("REMOTE_ADDR".to_string(), "127.0.0.1".to_string());
-    env_vars.insert("REMOTE_PORT".to_string(), "12345".to_string());
-    env_vars.insert("SERVER_ADDR".to_string(), "127.0.0.1".to_string());
-    env_vars.insert("SERVER_PORT".to_string(), "8080".to_string());
+    // The addresses of this connection, since the server may be bound to a wildcard address.
+    let (remote_address, local_address) = (stream.peer_addr()?, stream.local_addr()?);
+    env_vars.insert("REMOTE_ADDR".to_string(), remote_address.ip().to_string());
+    env_vars.insert("REMOTE_PORT".to_string(), remote_address.port().to_string());
+    env_vars.insert("SERVER_ADDR".to_string(), local_address.ip().to_string());
+    env_vars.insert("SERVER_PORT".to_string(), local_address.port().to_string());
     env_vars.insert("SERVER_NAME".to_string(), "localhost".to_string());
     env_vars.insert("DOCUMENT_ROOT".to_string(), ".".to_string());
-    env_vars.insert("SCRIPT_NAME".to_string(), path.to_string());
-    env_vars.insert("PATH_INFO".to_string(), "".to_string());
-    env_vars.insert("PATH_TRANSLATED".to_string(), script_path.clone());
+    env_vars.insert(
+        "SCRIPT_NAME".to_string(),
+        format!("/cgi-bin/{}", script_name),
+    );
+    env_vars.insert("PATH_INFO".to_string(), path_info.to_string());
+    env_vars.insert(
+        "PATH_TRANSLATED".to_string(),
+        absolute_script_path.display().to_string(),
+    );
 
     // Add HTTP headers as environment variables
     for (key, value) in headers {
@@ -133,73 +358,534 @@ fn handle_cgi_request(
 
     match output {
         Ok(mut child) => {
-            // Send any input to the CGI script
-            if let Some(mut stdin) = child.stdin.take() {
-                // For POST requests, you'd read the body here
-                stdin.write_all(b"").unwrap();
-            }
+            // Send the request body to the CGI script from another thread, since a script may write output before it
+            // has read all of its input, and would block once the unread output fills the pipe. Stdin is closed when
+            // the thread ends, so a script reading to EOF finishes. A script which exits without reading its input
+            // isn't an error.
+            let writer = child.stdin.take().map(|mut stdin| {
+                let body = body.to_vec();
+                thread::spawn(move || {
+                    if let Err(e) = stdin.write_all(&body) {
+                        eprintln!("Error writing request body to CGI script: {}", e);
+                    }
+                })
+            });
 
-            // Read the output
-            let mut stdout = child.stdout.take().unwrap();
-            let mut stderr = child.stderr.take().unwrap();
-
-            let mut output = Vec::new();
-            let mut error_output = Vec::new();
-
-            stdout.read_to_end(&mut output).unwrap();
-            stderr.read_to_end(&mut error_output).unwrap();
+            // Read stdout and stderr together, and wait for the process to complete
+            let output = child.wait_with_output();
+            if let Some(writer) = writer {
+                let _ = writer.join();
+            }
+            let output = match output {
+                Ok(output) => output,
+                Err(e) => {
+                    eprintln!("Error reading CGI script output: {}", e);
+                    return send_error(stream, "500 Internal Server Error", keep_alive);
+                }
+            };
 
-            // Wait for the process to complete
-            let status = child.wait().unwrap();
+            if !output.status.success() {
+                eprintln!("CGI script exited with status: {}", output.status);
+            }
 
             // Parse and send the CGI response
-            let response = String::from_utf8_lossy(&output);
-            send_cgi_response(stream, &response);
-
-            if !status.success() {
-                eprintln!("CGI script exited with status: {}", status);
-            }
+            send_cgi_response(stream, &output.stdout, keep_alive)
         }
         Err(e) => {
             eprintln!("Failed to execute CGI script: {}", e);
-            send_error(stream, "500 Internal Server Error");
+            send_error(stream, "500 Internal Server Error", keep_alive)
         }
     }
 }
 
-fn send_cgi_response(mut stream: TcpStream, cgi_output: &str) {
-    // Split the CGI output into headers and body
-    let parts: Vec<&str> = cgi_output.split("\r\n\r\n").collect();
+/// Decodes `%XX` escapes in a URL path, returning `None` if an escape is malformed or the result isn't UTF-8.
+fn percent_decode(path: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
 
-    if parts.len() >= 2 {
-        // CGI output has headers and body
-        let headers = parts[0];
-        let body = parts[1..].join("\r\n\r\n");
+/// Resolves the decoded `path` (relative to `/cgi-bin/`) to a script in `cgi_bin`, returning the script's name, the
+/// `PATH_INFO` following it and the script's canonical path, or the status to answer with.
+///
+/// Absolute paths and `..` segments are refused before anything is looked up, so the answer doesn't reveal which
+/// files exist outside cgi-bin. Scripts reached through a symlink must still resolve to a file inside cgi-bin.
+fn resolve_script<'a>(
+    cgi_bin: &Path,
+    path: &'a str,
+) -> Result<(&'a str, &'a str, PathBuf), &'static str> {
+    if path.starts_with('/') || path.split('/').any(|segment| segment == "..") {
+        return Err("403 Forbidden");
+    }
+    let (script_name, path_info) = split_script_path(cgi_bin, path).ok_or("404 Not Found")?;
 
-        // Send HTTP status line
-        stream.write_all(b"HTTP/1.1 200 OK\r\n").unwrap();
+    match cgi_bin.join(script_name).canonicalize() {
+        Ok(script_path)
+            if cgi_bin
+                .canonicalize()
+                .is_ok_and(|cgi_bin| script_path.starts_with(cgi_bin)) =>
+        {
+            Ok((script_name, path_info, script_path))
+        }
+        _ => Err("403 Forbidden"),
+    }
+}
 
-        // Send CGI headers
-        stream.write_all(headers.as_bytes()).unwrap();
-        stream.write_all(b"\r\n\r\n").unwrap();
+/// Splits `path` (relative to `/cgi-bin/`) into the longest prefix naming an executable file in `cgi_bin`, and the
+/// rest of the path, which is passed to the script as `PATH_INFO`. Returns `None` if no prefix names a script.
+fn split_script_path<'a>(cgi_bin: &Path, path: &'a str) -> Option<(&'a str, &'a str)> {
+    let mut boundaries: Vec<usize> = path
+        .match_indices('/')
+        .map(|(position, _)| position)
+        .collect();
+    boundaries.push(path.len());
 
-        // Send body
-        stream.write_all(body.as_bytes()).unwrap();
-    } else {
-        // No headers found, treat as raw content
-        stream
-            .write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n")
-            .unwrap();
-        stream.write_all(cgi_output.as_bytes()).unwrap();
+    boundaries
+        .into_iter()
+        .rev()
+        .map(|position| path.split_at(position))
+        .find(|(script_name, _)| is_executable(&cgi_bin.join(script_name)))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn send_cgi_response(
+    stream: &mut TcpStream,
+    cgi_output: &[u8],
+    keep_alive: bool,
+) -> std::io::Result<()> {
+    // Split the CGI output into headers and body, which is forwarded byte for byte.
+    match cgi_output
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+    {
+        Some(position) => {
+            let headers = String::from_utf8_lossy(&cgi_output[..position]);
+            let body = &cgi_output[position + 4..];
+
+            // Send HTTP status line
+            stream.write_all(b"HTTP/1.1 200 OK\r\n")?;
+
+            // The body is fully buffered, so its actual length replaces any the script declared, which lets the
+            // connection be reused. Whether it is reused is up to the server, so the script's `Connection` is
+            // replaced too.
+            let is_framing_header = |line: &str| {
+                let name = line
+                    .split(':')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_ascii_lowercase();
+                name == "content-length" || name == "connection"
+            };
+            for line in headers.lines().filter(|line| !is_framing_header(line)) {
+                write!(stream, "{}\r\n", line)?;
+            }
+            write!(stream, "Content-Length: {}\r\n", body.len())?;
+            stream.write_all(connection_header(keep_alive).as_bytes())?;
+            stream.write_all(b"\r\n")?;
+
+            // Send body
+            stream.write_all(body)
+        }
+        None => {
+            // No headers found, treat as raw content
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n{}\r\n",
+                cgi_output.len(),
+                connection_header(keep_alive)
+            )?;
+            stream.write_all(cgi_output)
+        }
     }
 }
 
-fn send_error(mut stream: TcpStream, status: &str) {
+fn send_error(stream: &mut TcpStream, status: &str, keep_alive: bool) -> std::io::Result<()> {
     let response = format!(
-        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n{}\r\n{}",
         status,
         status.len(),
+        connection_header(keep_alive),
         status
     );
-    stream.write_all(response.as_bytes()).unwrap();
+    stream.write_all(response.as_bytes())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::OnceLock;
+
+    const SCRIPTS: &[(&str, &str)] = &[
+        ("echo", "printf 'Content-Type: application/octet-stream\\r\\n\\r\\n'\ncat"),
+        ("echo-stderr", "printf 'Content-Type: application/octet-stream\\r\\n\\r\\n'\ntee /dev/stderr"),
+        ("cookie", "printf 'Content-Type: text/plain\\r\\n\\r\\n%s' \"$HTTP_COOKIE\""),
+        (
+            "framed",
+            "printf 'Content-Type: text/plain\\r\\nContent-Length: 999\\r\\nConnection: close\\r\\n\\r\\nbody'",
+        ),
+        ("large", "printf 'Content-Type: text/plain\\r\\n\\r\\n'\nhead -c 16777216 /dev/zero"),
+        (
+            "addresses",
+            "printf 'Content-Type: text/plain\\r\\n\\r\\n%s:%s %s:%s' \\\n\
+             \"$REMOTE_ADDR\" \"$REMOTE_PORT\" \"$SERVER_ADDR\" \"$SERVER_PORT\"",
+        ),
+        ("foo", PRINT_REQUEST),
+        ("nested/bar", PRINT_REQUEST),
+    ];
+
+    const PRINT_REQUEST: &str = "printf 'Content-Type: text/plain\\r\\n\\r\\n'\n\
+                                 echo \"QUERY_STRING=$QUERY_STRING\"\n\
+                                 echo \"SCRIPT_NAME=$SCRIPT_NAME\"\n\
+                                 echo \"PATH_INFO=$PATH_INFO\"";
+
+    // A cgi-bin holding `SCRIPTS`, written once before any test runs a script, since executing a file another thread
+    // still has open for writing fails.
+    fn cgi_bin() -> &'static Path {
+        static CGI_BIN: OnceLock<PathBuf> = OnceLock::new();
+        CGI_BIN.get_or_init(|| {
+            let cgi_bin =
+                std::env::temp_dir().join(format!("http-cgi-server-test-{}", std::process::id()));
+            for (name, script) in SCRIPTS {
+                let path = cgi_bin.join(name);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            cgi_bin
+        })
+    }
+
+    // Starts a server on an unused port, returning its address.
+    fn serve() -> SocketAddr {
+        serve_on(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    }
+
+    fn serve_on(ip: IpAddr) -> SocketAddr {
+        let listener = TcpListener::bind((ip, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = Config {
+            address: address.ip(),
+            port: address.port(),
+            cgi_bin: cgi_bin().to_path_buf(),
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                handle_connection(stream.unwrap(), &config);
+            }
+        });
+        address
+    }
+
+    // Reads one response, returning its status line, lowercased headers and body. No header may be repeated.
+    fn read_response(reader: &mut impl BufRead) -> (String, HashMap<String, String>, Vec<u8>) {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).unwrap();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let Some((key, value)) = line.trim_end().split_once(": ") else {
+                break;
+            };
+            let repeated = headers.insert(key.to_lowercase(), value.to_string());
+            assert!(repeated.is_none(), "{} is repeated", key);
+        }
+
+        let mut body = vec![0; headers["content-length"].parse().unwrap()];
+        reader.read_exact(&mut body).unwrap();
+        (status_line.trim_end().to_string(), headers, body)
+    }
+
+    // Requests `path` on a new connection, returning the response's status line and body.
+    fn get(path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(serve()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        write!(stream, "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n", path).unwrap();
+        let (status_line, _, body) = read_response(&mut reader);
+        (status_line, String::from_utf8(body).unwrap())
+    }
+
+    fn post(path: &str, body: &[u8]) -> Vec<u8> {
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            path,
+            body.len()
+        )
+        .into_bytes();
+        request.extend(body);
+        request
+    }
+
+    #[test]
+    fn reuses_a_connection_for_pipelined_requests() {
+        let mut stream = TcpStream::connect(serve()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // Both requests arrive in one write, so the second is read along with the first's body.
+        let binary = [0xff, 0xfe, b'\r', b'\n', b'\r', b'\n', 0x00];
+        stream
+            .write_all(
+                &[
+                    post("/cgi-bin/echo", b"first"),
+                    post("/cgi-bin/echo", &binary),
+                ]
+                .concat(),
+            )
+            .unwrap();
+
+        let (status_line, headers, body) = read_response(&mut reader);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(headers["connection"], "keep-alive");
+        assert_eq!(body, b"first");
+
+        let (status_line, _, body) = read_response(&mut reader);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(body, binary);
+
+        // And the connection is still usable once the pipeline drains.
+        stream.write_all(&post("/cgi-bin/echo", b"third")).unwrap();
+        assert_eq!(read_response(&mut reader).2, b"third");
+    }
+
+    #[test]
+    fn round_trips_a_post_body_larger_than_the_pipes() {
+        let address = serve();
+        // The scripts write their output while they read their input, which fills the pipes unless both are serviced.
+        let body: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+
+        for script in ["/cgi-bin/echo", "/cgi-bin/echo-stderr"] {
+            let mut stream = TcpStream::connect(address).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            stream.write_all(&post(script, &body)).unwrap();
+
+            let (status_line, _, response_body) = read_response(&mut reader);
+            assert_eq!(status_line, "HTTP/1.1 200 OK");
+            assert!(response_body == body, "{} echoed a different body", script);
+        }
+    }
+
+    #[test]
+    fn reads_a_head_with_a_4kb_cookie() {
+        let cookie = format!("session={}", "a".repeat(4096));
+        let request = format!(
+            "GET /cgi-bin/cookie HTTP/1.1\r\nCookie: {}\r\n\r\nbody",
+            cookie
+        );
+
+        // The head spans several reads, and the bytes after it are kept for the body.
+        let (buffer, header_end) = read_head(&mut request.as_bytes(), Vec::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            &buffer[..header_end],
+            request.strip_suffix("body").unwrap().as_bytes()
+        );
+        assert_eq!(&buffer[header_end..], b"body");
+
+        let mut stream = TcpStream::connect(serve()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream
+            .write_all(request.strip_suffix("body").unwrap().as_bytes())
+            .unwrap();
+        let (status_line, headers, body) = read_response(&mut reader);
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(headers["content-type"], "text/plain");
+        assert_eq!(body, cookie.as_bytes());
+    }
+
+    #[test]
+    fn refuses_a_head_larger_than_the_limit() {
+        let request = format!(
+            "GET / HTTP/1.1\r\nCookie: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_SIZE)
+        );
+        let error = read_head(&mut request.as_bytes(), Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn passes_the_query_string_to_the_script() {
+        let (status_line, body) = get("/cgi-bin/foo?x=1");
+        assert_eq!(status_line, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            "QUERY_STRING=x=1\nSCRIPT_NAME=/cgi-bin/foo\nPATH_INFO=\n"
+        );
+
+        let (_, body) = get("/cgi-bin/foo");
+        assert!(body.starts_with("QUERY_STRING=\n"));
+    }
+
+    #[test]
+    fn splits_the_script_from_the_path_info() {
+        let cgi_bin = cgi_bin();
+        assert_eq!(split_script_path(cgi_bin, "foo"), Some(("foo", "")));
+        assert_eq!(
+            split_script_path(cgi_bin, "foo/extra/path"),
+            Some(("foo", "/extra/path"))
+        );
+        assert_eq!(
+            split_script_path(cgi_bin, "nested/bar/extra"),
+            Some(("nested/bar", "/extra"))
+        );
+        // Directories and missing files aren't scripts.
+        assert_eq!(split_script_path(cgi_bin, "nested"), None);
+        assert_eq!(split_script_path(cgi_bin, "nested/missing/extra"), None);
+
+        let (_, body) = get("/cgi-bin/nested/bar/extra?x=1");
+        assert_eq!(
+            body,
+            "QUERY_STRING=x=1\nSCRIPT_NAME=/cgi-bin/nested/bar\nPATH_INFO=/extra\n"
+        );
+    }
+
+    #[test]
+    fn refuses_paths_escaping_cgi_bin() {
+        let cgi_bin = cgi_bin();
+        for path in [
+            "../../etc/passwd",
+            "nested/../../etc/passwd",
+            "nested/../foo",
+            "/bin/sh",
+            "foo/..",
+        ] {
+            assert_eq!(
+                resolve_script(cgi_bin, path),
+                Err("403 Forbidden"),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(resolve_script(cgi_bin, "missing"), Err("404 Not Found"));
+
+        let canonical = cgi_bin.join("nested/bar").canonicalize().unwrap();
+        assert_eq!(
+            resolve_script(cgi_bin, "nested/bar/extra"),
+            Ok(("nested/bar", "/extra", canonical))
+        );
+
+        // Whether or not the file outside cgi-bin exists, and however the `..` is spelled.
+        for path in [
+            "/cgi-bin/../../bin/sh",
+            "/cgi-bin/../../missing",
+            "/cgi-bin/%2e%2e/%2E%2E/bin/sh",
+        ] {
+            assert_eq!(get(path).0, "HTTP/1.1 403 Forbidden", "{}", path);
+        }
+        assert_eq!(get("/cgi-bin/nested/bar").0, "HTTP/1.1 200 OK");
+    }
+
+    #[test]
+    fn replaces_the_framing_headers_of_the_script() {
+        let mut stream = TcpStream::connect(serve()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        for _ in 0..2 {
+            stream
+                .write_all(b"GET /cgi-bin/framed HTTP/1.1\r\n\r\n")
+                .unwrap();
+            let (_, headers, body) = read_response(&mut reader);
+            assert_eq!(headers["content-length"], "4");
+            assert_eq!(headers["connection"], "keep-alive");
+            assert_eq!(body, b"body");
+        }
+    }
+
+    #[test]
+    fn survives_a_client_closing_mid_response() {
+        let address = serve();
+        // The response is far larger than the socket buffers, so writing it fails once the client is gone.
+        TcpStream::connect(address)
+            .unwrap()
+            .write_all(b"GET /cgi-bin/large HTTP/1.1\r\n\r\n")
+            .unwrap();
+
+        // The test server handles connections one at a time, so this is only answered if the last one ended cleanly.
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream.write_all(&post("/cgi-bin/echo", b"next")).unwrap();
+        assert_eq!(read_response(&mut reader).2, b"next");
+    }
+
+    #[test]
+    fn passes_the_connection_addresses_to_the_script() {
+        // Bound to the wildcard address, the script still sees the address the client connected to.
+        let port = serve_on(IpAddr::V4(Ipv4Addr::UNSPECIFIED)).port();
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        stream
+            .write_all(b"GET /cgi-bin/addresses HTTP/1.1\r\n\r\n")
+            .unwrap();
+
+        let client_port = stream.local_addr().unwrap().port();
+        let expected = format!("127.0.0.1:{} 127.0.0.1:{}", client_port, port);
+        assert_eq!(read_response(&mut reader).2, expected.as_bytes());
+    }
+
+    #[test]
+    fn refuses_invalid_arguments() {
+        let from_args = |args: &[&str]| Config::from_args(args.iter().map(|arg| arg.to_string()));
+
+        let config = from_args(&[
+            "--address",
+            "::",
+            "--port",
+            "80",
+            "--cgi-bin",
+            "/srv/cgi-bin",
+        ])
+        .unwrap();
+        assert_eq!(config.address, IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED));
+        assert_eq!(config.port, 80);
+        assert_eq!(config.cgi_bin, Path::new("/srv/cgi-bin"));
+
+        let error = from_args(&["--address", "localhost"]).err().unwrap();
+        assert_eq!(
+            error,
+            "Invalid bind address \"localhost\", expected an IP address"
+        );
+        let error = from_args(&["--port", "65536"]).err().unwrap();
+        assert_eq!(
+            error,
+            "Invalid port \"65536\", expected a number up to 65535"
+        );
+        assert_eq!(
+            from_args(&["--address"]).err().unwrap(),
+            "Missing value for --address"
+        );
+        assert_eq!(
+            from_args(&["--verbose"]).err().unwrap(),
+            "Unknown argument \"--verbose\""
+        );
+    }
 }
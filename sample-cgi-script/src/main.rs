@@ -6,12 +6,12 @@ use tower_cookies::{Cookie, Cookies};
 use tower_sessions::cookie::time::Duration;
 use tower_sessions::{MemoryStore, Session, SessionStore};
 use tower_sessions::session::Record;
-use tower_cgi::serve_cgi;
+use tower_cgi::{serve_cgi, CgiExit};
 
 use tower_sessions_file_based_store::FileStore;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> CgiExit {
     let session_store = FileStore::new("./", "prefix-", ".json");
     // let session_store = MemoryStore::default();
     let session_layer = tower_sessions::SessionManagerLayer::new(session_store)
@@ -56,8 +56,9 @@ async fn main() {
         get(|| async { "Hello, PATH_INFO" }),
     ).layer(session_layer);
 
-    if let Err(e) = serve_cgi(app).await {
+    let result = serve_cgi(app).await;
+    if let Err(e) = &result {
         eprintln!("Error while serving CGI request: {}", e);
-        std::process::exit(1);
     }
+    CgiExit::from_result(&result)
 }
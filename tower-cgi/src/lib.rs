@@ -16,69 +16,1034 @@
 //! }
 //! ```
 
-use cgi_rs::{CGIError, CGIRequest, CGIResponse};
+use cgi_rs::body::ReaderBody;
+use cgi_rs::request::BodyOptions;
+use cgi_rs::response::BoxError;
+use cgi_rs::{CGIError, CGIRequest, CGIResponse, EnvSource, MetaVariableKind, ResponseBody};
+use http_body_util::Full;
+use hyper::body::{Body, Bytes};
+use hyper::{Request, Response, StatusCode};
 use snafu::ResultExt;
-use std::convert::Infallible;
+use std::convert::identity;
 use std::fmt::Debug;
+use std::future::Future;
 use std::io::Write;
-use http_body_util::{Full, BodyExt};
-use hyper::body::{Body, Bytes};
-use hyper::{Request, Response};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::process::{ExitCode, Termination};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::AsyncRead;
 use tower::{Service, ServiceExt};
 
+/// Statistics about a served CGI request, suitable for logging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServeStats {
+    /// Time from the request being read from the environment until the response had been written.
+    pub elapsed: Duration,
+    /// The number of request body bytes read from stdin.
+    pub request_body_len: usize,
+}
+
+/// How a CGI script should exit once it has served a request.
+///
+/// Some servers discard the output of a script which exits with a non-zero status, so a script should exit
+/// successfully whenever a response was written, including error responses such as `500 Internal Server Error`, and
+/// fail only when nothing usable was written. `CgiExit` implements [`Termination`], so `main` can return it:
+///
+/// ```rust
+/// use axum::{routing::get, Router};
+/// use tower_cgi::{serve_cgi, CgiExit, CgiServiceError};
+///
+/// #[tokio::main]
+/// async fn main() -> CgiExit {
+///     # std::env::set_var("REQUEST_METHOD", "GET");
+///     # std::env::set_var("REQUEST_URI", "/");
+///     let app = Router::new().route("/", get(|| async { "Hello, World!" }));
+///     let result = serve_cgi(app).await;
+///     if let Err(e) = &result {
+///         eprintln!("Error while serving CGI request: {}", e);
+///     }
+///     # let timeout = CgiServiceError::Timeout { timeout: std::time::Duration::from_secs(1) };
+///     # assert_eq!(CgiExit::from_result(&Err(timeout)), CgiExit::ErrorResponse);
+///     # assert_eq!(CgiExit::from_result(&result), CgiExit::Served);
+///     CgiExit::from_result(&result)
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgiExit {
+    /// The application's response was written.
+    Served,
+    /// The application failed or timed out, and an error response was written in its place.
+    ErrorResponse,
+    /// Serving failed before a complete response was written.
+    NoResponse,
+}
+
+impl CgiExit {
+    /// Classifies the result of serving a request.
+    pub fn from_result(result: &Result<ServeStats>) -> CgiExit {
+        match result {
+            Ok(_) => CgiExit::Served,
+            Err(e) if e.response_written() => CgiExit::ErrorResponse,
+            Err(_) => CgiExit::NoResponse,
+        }
+    }
+
+    /// Returns the process exit code: success if a response was written, failure otherwise.
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            CgiExit::Served | CgiExit::ErrorResponse => ExitCode::SUCCESS,
+            CgiExit::NoResponse => ExitCode::FAILURE,
+        }
+    }
+}
+
+impl Termination for CgiExit {
+    fn report(self) -> ExitCode {
+        self.exit_code()
+    }
+}
+
 /// Serve a CGI application.
 ///
 /// Responses are emitted to stdout per the CGI RFC3875
-pub async fn serve_cgi<S, B>(app: S) -> Result<()>
+pub async fn serve_cgi<S, B>(app: S) -> Result<ServeStats>
 where
-    S: Service<Request<Full<Bytes>>, Response = Response<B>, Error = Infallible>
-        + Clone
-        + Send
-        + 'static,
-    B: Body, <B as Body>::Error: Debug
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
 {
-    serve_cgi_with_output(std::io::stdout(), app).await
+    serve(
+        Output::<std::io::Stdout>::Stdout,
+        app,
+        &Settings::default(),
+        buffered_request(BodyOptions::default()),
+        identity,
+        CGIResponse::from_hyper,
+    )
+    .await
 }
 
 /// Serve a CGI application.
 ///
-/// Responses are emitted to the provided output stream.
-pub async fn serve_cgi_with_output<S, B>(output: impl Write, app: S) -> Result<()>
+/// Responses are emitted to the provided output stream. Requests whose URI exceeds the length limit are answered with
+/// `414 URI Too Long` without calling the application, in the format preferred by the request's `Accept` header.
+///
+/// ```rust
+/// use axum::{routing::get, Router};
+/// use tower_cgi::serve_cgi_with_output;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", format!("/{}", "a".repeat(10_000)));
+///
+/// let app = Router::new().route("/", get(|| async { "Hello, World!" }));
+/// let mut output = Vec::new();
+/// serve_cgi_with_output(&mut output, app).await.unwrap();
+///
+/// assert!(output.starts_with(b"Status: 414 URI Too Long\r\n"));
+/// # }
+/// ```
+///
+/// Redirects returned by the application keep their status, written with a single reason phrase, and `Location`
+/// header:
+///
+/// ```rust
+/// use axum::{response::Redirect, routing::get, Router};
+/// use tower_cgi::serve_cgi_with_output;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = Router::new().route("/", get(|| async { Redirect::to("/login") }));
+/// let mut output = Vec::new();
+/// serve_cgi_with_output(&mut output, app).await.unwrap();
+///
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with("Status: 303 See Other\r\n"));
+/// assert!(output.contains("location: /login\r\n"));
+/// # }
+/// ```
+///
+/// The application's status is always written, so a default-constructed response (`200 OK`, as for handlers returning
+/// `()`) is explicitly a `200 OK`:
+///
+/// ```rust
+/// use std::convert::Infallible;
+/// use axum::{body::Body, response::Response};
+/// use tower::service_fn;
+/// use tower_cgi::serve_cgi_with_output;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = service_fn(|_| async { Ok::<_, Infallible>(Response::<Body>::default()) });
+/// let mut output = Vec::new();
+/// serve_cgi_with_output(&mut output, app).await.unwrap();
+///
+/// assert_eq!(output, b"Status: 200 OK\r\ncontent-length: 0\r\n\r\n");
+/// # }
+/// ```
+///
+/// If the application fails, a `500 Internal Server Error` response is written and its error is returned as
+/// [`CgiServiceError::Service`]:
+///
+/// ```rust
+/// use std::io;
+/// use axum::{body::Body, response::Response};
+/// use tower::service_fn;
+/// use tower_cgi::{serve_cgi_with_output, CgiServiceError};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = service_fn(|_| async { Err::<Response<Body>, _>(io::Error::other("database unavailable")) });
+/// let mut output = Vec::new();
+/// let result = serve_cgi_with_output(&mut output, app).await;
+///
+/// assert!(matches!(result, Err(CgiServiceError::Service { .. })));
+/// assert!(output.starts_with(b"Status: 500 Internal Server Error\r\n"));
+/// # }
+/// ```
+///
+/// A panicking application is answered the same way, with its panic message returned in [`CgiServiceError::Panic`]:
+///
+/// ```rust
+/// use axum::{routing::get, Router};
+/// use tower_cgi::{serve_cgi_with_output, CgiServiceError};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = Router::new().route("/", get(|| async {
+///     let names: Vec<&str> = Vec::new();
+///     names[0]
+/// }));
+/// let mut output = Vec::new();
+/// let result = serve_cgi_with_output(&mut output, app).await;
+///
+/// assert!(matches!(result, Err(CgiServiceError::Panic { message }) if message.starts_with("index out of bounds")));
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with("Status: 500 Internal Server Error\r\n"));
+/// assert!(output.contains("\r\n\r\n"));
+/// # }
+/// ```
+///
+/// Likewise, if the response body fails while it's collected, a `500 Internal Server Error` response is written in
+/// place of the partial body and the error is returned as [`CgiServiceError::ResponseBodyCollect`]. Unless a logger is
+/// set with [`CgiServer::with_logger`], the error is also written to stderr, which servers keep in their error log:
+///
+/// ```rust
+/// use std::convert::Infallible;
+/// use std::io;
+/// use std::pin::Pin;
+/// use std::task::{Context, Poll};
+/// use hyper::body::{Body, Bytes, Frame};
+/// use hyper::Response;
+/// use tower::service_fn;
+/// use tower_cgi::{serve_cgi_with_output, CgiServiceError};
+///
+/// struct FailingBody;
+///
+/// impl Body for FailingBody {
+///     type Data = Bytes;
+///     type Error = io::Error;
+///
+///     fn poll_frame(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+///         Poll::Ready(Some(Err(io::Error::other("upstream closed"))))
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = service_fn(|_| async { Ok::<_, Infallible>(Response::new(FailingBody)) });
+/// let mut output = Vec::new();
+/// let result = serve_cgi_with_output(&mut output, app).await;
+///
+/// assert!(matches!(result, Err(CgiServiceError::ResponseBodyCollect { .. })));
+/// assert!(output.starts_with(b"Status: 500 Internal Server Error\r\n"));
+/// # }
+/// ```
+pub async fn serve_cgi_with_output<S, B>(output: impl Write, app: S) -> Result<ServeStats>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    serve(
+        Output::Writer(output),
+        app,
+        &Settings::default(),
+        buffered_request(BodyOptions::default()),
+        identity,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// Serve a CGI application which expects a request body other than `Full<Bytes>`, such as a boxed body.
+///
+/// The request body is read from stdin like [`serve_cgi`], then converted with `into_body`, e.g.
+/// [`BodyExt::boxed_unsync`](http_body_util::BodyExt::boxed_unsync) or `axum::body::Body::new`.
+pub async fn serve_cgi_with_body<ReqB, S, B>(
+    app: S,
+    into_body: impl FnOnce(Full<Bytes>) -> ReqB,
+) -> Result<ServeStats>
 where
-    S: Service<Request<Full<Bytes>>, Response = Response<B>, Error = Infallible>
-        + Clone
-        + Send
-        + 'static,
-    B: Body, <B as Body>::Error: Debug
+    S: Service<Request<ReqB>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
 {
-    let request = CGIRequest::<Full<Bytes>>::from_env()
-        .and_then(Request::try_from)
-        .context(error::CGIRequestParseSnafu)?;
+    serve(
+        Output::<std::io::Stdout>::Stdout,
+        app,
+        &Settings::default(),
+        buffered_request(BodyOptions::default()),
+        into_body,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// Serve a CGI application like [`serve_cgi_with_body`], emitting the response to the provided output stream.
+///
+/// ```rust
+/// use std::convert::Infallible;
+/// use http_body_util::{combinators::UnsyncBoxBody, BodyExt};
+/// use hyper::{body::Bytes, Request, Response};
+/// use tower::service_fn;
+/// use tower_cgi::serve_cgi_with_body_and_output;
+///
+/// type BoxBody = UnsyncBoxBody<Bytes, Infallible>;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+/// std::env::set_var("CONTENT_LENGTH", "0");
+///
+/// let app = service_fn(|request: Request<BoxBody>| async move {
+///     let body = request.into_body().collect().await?.to_bytes();
+///     Ok::<_, Infallible>(Response::new(format!("{} byte body", body.len())))
+/// });
+/// let mut output = Vec::new();
+/// serve_cgi_with_body_and_output(&mut output, app, BodyExt::boxed_unsync).await.unwrap();
+///
+/// assert!(output.ends_with(b"\r\n\r\n0 byte body"));
+/// # }
+/// ```
+pub async fn serve_cgi_with_body_and_output<ReqB, S, B>(
+    output: impl Write,
+    app: S,
+    into_body: impl FnOnce(Full<Bytes>) -> ReqB,
+) -> Result<ServeStats>
+where
+    S: Service<Request<ReqB>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    serve(
+        Output::Writer(output),
+        app,
+        &Settings::default(),
+        buffered_request(BodyOptions::default()),
+        into_body,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// Serve a CGI application with the request read from `env` and the async `body` rather than the process environment
+/// and stdin, emitting the response to the provided output stream.
+///
+/// This allows testing an application's CGI behavior without mutating the process environment, and handling CGI
+/// requests from within a larger server.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use axum::{routing::post, Router};
+/// use tower_cgi::serve_cgi_with_env;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let env = HashMap::from([
+///     ("REQUEST_METHOD", "POST"),
+///     ("REQUEST_URI", "/echo"),
+///     ("CONTENT_LENGTH", "5"),
+///     ("CONTENT_TYPE", "text/plain"),
+/// ]);
+/// let app = Router::new().route("/echo", post(|body: String| async move { body.to_uppercase() }));
+///
+/// let mut output = Vec::new();
+/// let stats = serve_cgi_with_env(env, &b"hello"[..], &mut output, app).await.unwrap();
+///
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.starts_with("Status: 200 OK\r\n"));
+/// assert!(output.ends_with("\r\n\r\nHELLO"));
+/// assert_eq!(stats.request_body_len, 5);
+/// # }
+/// ```
+pub async fn serve_cgi_with_env<S, B>(
+    env: impl EnvSource + 'static,
+    body: impl AsyncRead + Unpin,
+    output: impl Write,
+    app: S,
+) -> Result<ServeStats>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    let cgi_request = CGIRequest::<Full<Bytes>>::from_source_with_async_reader(env, body);
+    serve(
+        Output::Writer(output),
+        app,
+        &Settings::default(),
+        cgi_request,
+        identity,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// Serve a CGI application, answering with `504 Gateway Timeout` if it doesn't respond within `timeout`.
+///
+/// See [`CgiServer::with_timeout`].
+pub async fn serve_cgi_with_timeout<S, B>(timeout: Duration, app: S) -> Result<ServeStats>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    CgiServer::new(app).with_timeout(timeout).serve().await
+}
+
+/// A configurable way to serve a CGI application, for options beyond those of [`serve_cgi`].
+///
+/// ```rust
+/// use std::time::Duration;
+/// use axum::{routing::get, Router};
+/// use tower_cgi::{CgiServer, CgiServiceError};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = Router::new().route("/", get(|| async {
+///     tokio::time::sleep(Duration::from_secs(10)).await;
+///     "Too late"
+/// }));
+/// let mut output = Vec::new();
+/// let result = CgiServer::new(app)
+///     .with_timeout(Duration::from_millis(50))
+///     .serve_with_output(&mut output)
+///     .await;
+///
+/// assert!(matches!(result, Err(CgiServiceError::Timeout { .. })));
+/// assert!(output.starts_with(b"Status: 504 Gateway Timeout\r\n"));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CgiServer<S> {
+    app: S,
+    settings: Settings,
+}
+
+impl<S, B> CgiServer<S>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    /// Creates a server for `app` with the same behavior as [`serve_cgi`].
+    pub fn new(app: S) -> Self {
+        CgiServer {
+            app,
+            settings: Settings::default(),
+        }
+    }
+
+    /// Answers with `504 Gateway Timeout` and returns [`CgiServiceError::Timeout`] if the application doesn't respond
+    /// within `timeout`, rather than holding the server's process open indefinitely. The application's unfinished
+    /// future is dropped.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.timeout = Some(timeout);
+        self
+    }
 
-    let response = app
-        .oneshot(request)
+    /// Answers with `408 Request Timeout` and returns [`CgiServiceError::BodyTimeout`] if the request body isn't read
+    /// within `timeout`, so a client stalling mid-upload can't hold the process open. The application isn't called.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::time::Duration;
+    /// use axum::{routing::post, Router};
+    /// use tower_cgi::{CgiServer, CgiServiceError};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST"), ("REQUEST_URI", "/"), ("CONTENT_LENGTH", "1024")]);
+    /// // A client which never sends its body.
+    /// let (_client, stalled_body) = tokio::io::duplex(64);
+    ///
+    /// let app = Router::new().route("/", post(|body: String| async move { body }));
+    /// let mut output = Vec::new();
+    /// let result = CgiServer::new(app)
+    ///     .with_body_timeout(Duration::from_millis(50))
+    ///     .serve_with_env(env, stalled_body, &mut output)
+    ///     .await;
+    ///
+    /// assert!(matches!(result, Err(CgiServiceError::BodyTimeout { .. })));
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert!(output.starts_with("Status: 408 Request Timeout\r\n"));
+    /// assert!(output.contains("connection: close\r\n"));
+    /// # }
+    /// ```
+    pub fn with_body_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.body_timeout = Some(timeout);
+        self
+    }
+
+    /// Reads the request body as `options` describe, e.g. to refuse bodies over a size limit or insist on the body
+    /// matching `CONTENT_LENGTH`. A body which doesn't fit them fails with [`CgiServiceError::CGIRequestParse`] before
+    /// the application is called.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use axum::{routing::post, Router};
+    /// use cgi_rs::{request::BodyOptions, CGIError};
+    /// use tower_cgi::{CgiServer, CgiServiceError};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let env = HashMap::from([("REQUEST_METHOD", "POST"), ("REQUEST_URI", "/"), ("CONTENT_LENGTH", "11")]);
+    ///
+    /// let app = Router::new().route("/", post(|body: String| async move { body }));
+    /// let result = CgiServer::new(app)
+    ///     .with_body_options(BodyOptions { max: 8, ..BodyOptions::default() })
+    ///     .serve_with_env(env, &b"hello world"[..], Vec::new())
+    ///     .await;
+    ///
+    /// assert!(matches!(
+    ///     result,
+    ///     Err(CgiServiceError::CGIRequestParse { source: CGIError::ContentTooLarge { limit: 8, .. } })
+    /// ));
+    /// # }
+    /// ```
+    pub fn with_body_options(mut self, options: BodyOptions) -> Self {
+        self.settings.body_options = options;
+        self
+    }
+
+    /// Calls `logger` with every error that serving the request returns, so they can be routed to syslog, a file or
+    /// `tracing` rather than left to the caller of [`CgiServer::serve`]. The error is still returned as well, but
+    /// [`CgiServiceError::ResponseBodyCollect`] is then no longer written to stderr.
+    ///
+    /// ```rust
+    /// use std::sync::{Arc, Mutex};
+    /// use axum::{http::StatusCode, routing::get, Router};
+    /// use tower_cgi::{CgiServer, CgiServiceError};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// std::env::set_var("REQUEST_METHOD", "GET");
+    /// std::env::set_var("REQUEST_URI", "/");
+    ///
+    /// // A forced error: the status line can't be written to a closed output.
+    /// struct Closed;
+    /// impl std::io::Write for Closed {
+    ///     fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+    ///         Err(std::io::ErrorKind::BrokenPipe.into())
+    ///     }
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let logged = Arc::new(Mutex::new(Vec::new()));
+    /// let log = logged.clone();
+    /// let app = Router::new().route("/", get(|| async { StatusCode::NO_CONTENT }));
+    /// let result = CgiServer::new(app)
+    ///     .with_logger(move |e| log.lock().unwrap().push(e.to_string()))
+    ///     .serve_with_output(Closed)
+    ///     .await;
+    ///
+    /// assert!(matches!(result, Err(CgiServiceError::CGIResponseWrite { .. })));
+    /// assert_eq!(logged.lock().unwrap().len(), 1);
+    /// # }
+    /// ```
+    pub fn with_logger(
+        mut self,
+        logger: impl Fn(&CgiServiceError) + Send + Sync + 'static,
+    ) -> Self {
+        self.settings.logger = Some(Arc::new(logger));
+        self
+    }
+
+    /// Serves the request, emitting the response to stdout.
+    pub async fn serve(self) -> Result<ServeStats> {
+        let output = Output::<std::io::Stdout>::Stdout;
+        let cgi_request = buffered_request(self.settings.body_options);
+        serve(
+            output,
+            self.app,
+            &self.settings,
+            cgi_request,
+            identity,
+            CGIResponse::from_hyper,
+        )
         .await
-        .expect("The Error type is Infallible, this should never fail.");
+    }
 
-    let headers = response.headers().clone();
-    let status = response.status().to_string();
-    let reason = response.status().canonical_reason().map(|s| s.to_string());
+    /// Serves the request, emitting the response to `output`.
+    pub async fn serve_with_output(self, output: impl Write) -> Result<ServeStats> {
+        let output = Output::Writer(output);
+        let cgi_request = buffered_request(self.settings.body_options);
+        serve(
+            output,
+            self.app,
+            &self.settings,
+            cgi_request,
+            identity,
+            CGIResponse::from_hyper,
+        )
+        .await
+    }
 
-    let collected = response.into_body().collect().await;
+    /// Serves the request read from `env` and `body` rather than the process environment and stdin, emitting the
+    /// response to `output`. See [`serve_cgi_with_env`].
+    pub async fn serve_with_env(
+        self,
+        env: impl EnvSource + 'static,
+        body: impl AsyncRead + Unpin,
+        output: impl Write,
+    ) -> Result<ServeStats> {
+        let output = Output::Writer(output);
+        let options = self.settings.body_options;
+        let cgi_request = CGIRequest::<Full<Bytes>>::from_source_with_async_reader_and_options(
+            env, body, options,
+        );
+        serve(
+            output,
+            self.app,
+            &self.settings,
+            cgi_request,
+            identity,
+            CGIResponse::from_hyper,
+        )
+        .await
+    }
+}
 
-    let body_bytes = collected.unwrap().to_bytes();
+/// Serve a CGI application, streaming its response body.
+///
+/// Unlike [`serve_cgi`], the status and headers are written as soon as the application responds, and each body frame
+/// is written and flushed as it arrives, so large downloads and event streams aren't held in memory.
+pub async fn serve_cgi_streaming<S, B>(app: S) -> Result<ServeStats>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    serve(
+        Output::<std::io::Stdout>::Stdout,
+        app,
+        &Settings::default(),
+        buffered_request(BodyOptions::default()),
+        identity,
+        stream_response,
+    )
+    .await
+}
+
+/// Serve a CGI application, streaming its response body to the provided output stream.
+///
+/// ```rust
+/// use std::io::{self, Read, Write};
+/// use std::convert::Infallible;
+/// use cgi_rs::body::ReaderBody;
+/// use hyper::{Request, Response};
+/// use tower::service_fn;
+/// use tower_cgi::serve_cgi_streaming_with_output;
+///
+/// // A reader producing one 5 byte chunk per read, so the body has several frames.
+/// struct Chunks(&'static [u8]);
+///
+/// impl Read for Chunks {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         let n = self.0.len().min(buf.len()).min(5);
+///         buf[..n].copy_from_slice(&self.0[..n]);
+///         self.0 = &self.0[n..];
+///         Ok(n)
+///     }
+/// }
+///
+/// // Records what had been written at each flush.
+/// #[derive(Default)]
+/// struct Flushes(Vec<u8>, Vec<Vec<u8>>);
+///
+/// impl Write for Flushes {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         self.0.extend_from_slice(buf);
+///         Ok(buf.len())
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         if !self.0.is_empty() {
+///             self.1.push(std::mem::take(&mut self.0));
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "GET");
+/// std::env::set_var("REQUEST_URI", "/");
+///
+/// let app = service_fn(|_: Request<_>| async {
+///     Ok::<_, Infallible>(Response::new(ReaderBody::new(Chunks(b"firstsecond"), 11)))
+/// });
+/// let mut output = Flushes::default();
+/// serve_cgi_streaming_with_output(&mut output, app).await.unwrap();
+///
+/// assert!(output.1[0].starts_with(b"Status: 200 OK\r\n"));
+/// assert_eq!(output.1[1..], [b"first".to_vec(), b"secon".to_vec(), b"d".to_vec()]);
+/// # }
+/// ```
+pub async fn serve_cgi_streaming_with_output<S, B>(output: impl Write, app: S) -> Result<ServeStats>
+where
+    S: Service<Request<Full<Bytes>>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let cgi_request = buffered_request(BodyOptions::default());
+    serve(
+        Output::Writer(output),
+        app,
+        &Settings::default(),
+        cgi_request,
+        identity,
+        stream_response,
+    )
+    .await
+}
+
+/// Serve a CGI application whose request body is read from stdin only as the application polls it.
+///
+/// The application can inspect the request's headers and reject it before any of the body is read, much like a server
+/// deciding whether to send `100 Continue`. The interim `100 Continue` response itself can't be sent from a CGI script;
+/// whether the client waits for it is up to the server. [`ServeStats::request_body_len`] is always 0, as the body is
+/// consumed by the application.
+pub async fn serve_cgi_lazy_body<S, B>(app: S) -> Result<ServeStats>
+where
+    S: Service<Request<LazyBody>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    serve(
+        Output::<std::io::Stdout>::Stdout,
+        app,
+        &Settings::default(),
+        lazy_request(),
+        identity,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// Serve a CGI application like [`serve_cgi_lazy_body`], emitting the response to the provided output stream.
+///
+/// ```rust
+/// use std::convert::Infallible;
+/// use hyper::{header::CONTENT_TYPE, Request, Response, StatusCode};
+/// use tower::service_fn;
+/// use tower_cgi::{serve_cgi_lazy_body_with_output, LazyBody};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// std::env::set_var("REQUEST_METHOD", "POST");
+/// std::env::set_var("REQUEST_URI", "/upload");
+/// std::env::set_var("CONTENT_TYPE", "application/zip");
+/// std::env::set_var("CONTENT_LENGTH", "1000000");
+///
+/// // Uploads other than images are refused without reading their body from stdin.
+/// let app = service_fn(|request: Request<LazyBody>| async move {
+///     let status = match request.headers().get(CONTENT_TYPE) {
+///         Some(content_type) if content_type.as_bytes().starts_with(b"image/") => StatusCode::CREATED,
+///         _ => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+///     };
+///     let mut response = Response::new(String::new());
+///     *response.status_mut() = status;
+///     Ok::<_, Infallible>(response)
+/// });
+/// let mut output = Vec::new();
+/// let stats = serve_cgi_lazy_body_with_output(&mut output, app).await.unwrap();
+///
+/// assert!(output.starts_with(b"Status: 415 Unsupported Media Type\r\n"));
+/// assert_eq!(stats.request_body_len, 0);
+/// # }
+/// ```
+pub async fn serve_cgi_lazy_body_with_output<S, B>(output: impl Write, app: S) -> Result<ServeStats>
+where
+    S: Service<Request<LazyBody>, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    serve(
+        Output::Writer(output),
+        app,
+        &Settings::default(),
+        lazy_request(),
+        identity,
+        CGIResponse::from_hyper,
+    )
+    .await
+}
+
+/// The request body passed to applications by [`serve_cgi_lazy_body`], which reads stdin as it is polled.
+pub type LazyBody = ReaderBody<std::io::Stdin>;
+
+async fn buffered_request(
+    options: BodyOptions,
+) -> std::result::Result<CGIRequest<Full<Bytes>>, CGIError> {
+    CGIRequest::<Full<Bytes>>::from_env_async_with_options(options).await
+}
 
-    let cgi_response = CGIResponse {
-        headers,
-        status,
-        reason,
-        body: body_bytes,
+async fn lazy_request() -> std::result::Result<CGIRequest<LazyBody>, CGIError> {
+    CGIRequest::<Full<Bytes>>::from_env_streaming()
+}
+
+// Polls the application's future, turning a panic in it into `CgiServiceError::Panic`. The panic hook has already
+// logged the message to stderr by then.
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F> Future for CatchUnwind<F>
+where
+    F: Future,
+{
+    type Output = Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.0.as_mut().poll(cx))) {
+            Ok(poll) => poll.map(Ok),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| String::from("Box<dyn Any>"));
+                Poll::Ready(Err(CgiServiceError::Panic { message }))
+            }
+        }
+    }
+}
+
+// Where the response is written. Stdout is written asynchronously, so the runtime isn't blocked.
+enum Output<W> {
+    Writer(W),
+    Stdout,
+}
+
+type Logger = Arc<dyn Fn(&CgiServiceError) + Send + Sync>;
+
+// Options for serving a request, set through `CgiServer`.
+#[derive(Clone, Default)]
+struct Settings {
+    timeout: Option<Duration>,
+    body_timeout: Option<Duration>,
+    body_options: BodyOptions,
+    logger: Option<Logger>,
+}
+
+impl Debug for Settings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Settings")
+            .field("timeout", &self.timeout)
+            .field("body_timeout", &self.body_timeout)
+            .field("body_options", &self.body_options)
+            .field(
+                "logger",
+                &self.logger.as_ref().map(|_| "Fn(&CgiServiceError)"),
+            )
+            .finish()
+    }
+}
+
+// `RB` is the body the request is read with, and `ReqB` the body the application expects.
+async fn serve<S, RB, ReqB, B, F, Fut>(
+    output: Output<impl Write>,
+    app: S,
+    settings: &Settings,
+    cgi_request: impl Future<Output = std::result::Result<CGIRequest<RB>, CGIError>>,
+    into_body: impl FnOnce(RB) -> ReqB,
+    into_cgi_response: F,
+) -> Result<ServeStats>
+where
+    S: Service<Request<ReqB>, Response = Response<B>>,
+    S::Error: Into<BoxError>,
+    RB: Body,
+    F: FnOnce(Response<B>) -> Fut,
+    Fut: Future<Output = std::result::Result<CGIResponse, CGIError>>,
+{
+    let result = try_serve(
+        output,
+        app,
+        settings,
+        cgi_request,
+        into_body,
+        into_cgi_response,
+    )
+    .await;
+    match (&result, &settings.logger) {
+        (Err(e), Some(logger)) => logger(e),
+        // The client only sees the 500, so leave the cause in the server's error log.
+        (Err(e @ CgiServiceError::ResponseBodyCollect { .. }), None) => eprintln!("{}", e),
+        _ => {}
+    }
+    result
+}
+
+async fn try_serve<S, RB, ReqB, B, F, Fut>(
+    output: Output<impl Write>,
+    app: S,
+    settings: &Settings,
+    cgi_request: impl Future<Output = std::result::Result<CGIRequest<RB>, CGIError>>,
+    into_body: impl FnOnce(RB) -> ReqB,
+    into_cgi_response: F,
+) -> Result<ServeStats>
+where
+    S: Service<Request<ReqB>, Response = Response<B>>,
+    S::Error: Into<BoxError>,
+    RB: Body,
+    F: FnOnce(Response<B>) -> Fut,
+    Fut: Future<Output = std::result::Result<CGIResponse, CGIError>>,
+{
+    let cgi_request = match settings.body_timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, cgi_request).await {
+            Ok(cgi_request) => cgi_request,
+            Err(_) => {
+                write_response(output, CGIResponse::request_timeout()).await?;
+                return Err(CgiServiceError::BodyTimeout { timeout });
+            }
+        },
+        None => cgi_request.await,
+    }
+    .context(error::CGIRequestParseSnafu)?;
+    let received_at = cgi_request.received_at();
+    let request_body_len = cgi_request.body_len();
+    let accept = cgi_request
+        .var(MetaVariableKind::HttpAccept)
+        .and_then(|accept| accept.as_str().ok().map(str::to_string));
+    // An error to return once the error response has been written.
+    let mut failure = None;
+    let cgi_response = match Request::try_from(cgi_request) {
+        Ok(request) => {
+            // On timeout the application's future is dropped, cancelling it at its current await point.
+            let response = CatchUnwind(Box::pin(app.oneshot(request.map(into_body))));
+            let response = match settings.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, response)
+                    .await
+                    .unwrap_or_else(|_| Err(CgiServiceError::Timeout { timeout })),
+                None => response.await,
+            };
+            match response {
+                Ok(Ok(response)) => match into_cgi_response(response).await {
+                    Ok(cgi_response) => cgi_response,
+                    Err(source @ CGIError::ReadResponseBody { .. }) => {
+                        failure = Some(CgiServiceError::ResponseBodyCollect { source });
+                        CGIResponse::error_page(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            accept.as_deref(),
+                        )
+                    }
+                    Err(source) => return Err(CgiServiceError::CGIResponseParse { source }),
+                },
+                Ok(Err(source)) => {
+                    failure = Some(CgiServiceError::Service {
+                        source: source.into(),
+                    });
+                    CGIResponse::error_page(StatusCode::INTERNAL_SERVER_ERROR, accept.as_deref())
+                }
+                Err(e) => {
+                    let status = match e {
+                        CgiServiceError::Timeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+                        _ => StatusCode::INTERNAL_SERVER_ERROR,
+                    };
+                    failure = Some(e);
+                    CGIResponse::error_page(status, accept.as_deref())
+                }
+            }
+        }
+        Err(CGIError::UriTooLong { .. }) => {
+            CGIResponse::error_page(StatusCode::URI_TOO_LONG, accept.as_deref())
+        }
+        Err(source) => return Err(CgiServiceError::CGIRequestParse { source }),
     };
 
-    cgi_response
-        .write_response_to_output(output)
-        .await
-        .context(error::CGIResponseWriteSnafu)
+    write_response(output, cgi_response).await?;
+
+    if let Some(failure) = failure {
+        return Err(failure);
+    }
+
+    Ok(ServeStats {
+        elapsed: received_at.elapsed(),
+        request_body_len,
+    })
+}
+
+async fn write_response(output: Output<impl Write>, cgi_response: CGIResponse) -> Result<()> {
+    match output {
+        Output::Writer(output) => match cgi_response.body {
+            ResponseBody::Buffered(_) => cgi_response.write_response_to_output(output),
+            ResponseBody::Stream(_) => {
+                cgi_response
+                    .write_streaming_response_to_output(output)
+                    .await
+            }
+        },
+        Output::Stdout => {
+            cgi_response
+                .write_response_to_output_async(tokio::io::stdout())
+                .await
+        }
+    }
+    .context(error::CGIResponseWriteSnafu)
+}
+
+async fn stream_response<B>(response: Response<B>) -> std::result::Result<CGIResponse, CGIError>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    Ok(CGIResponse::from_streaming(response))
 }
 
 mod error {
@@ -96,8 +1061,49 @@ mod error {
 
         #[snafu(display("Failed to write CGI response: {}", source))]
         CGIResponseWrite { source: CGIError },
+
+        #[snafu(display(
+            "Response body failed, answered with 500 Internal Server Error: {}",
+            source
+        ))]
+        ResponseBodyCollect { source: CGIError },
+
+        #[snafu(display("Service failed, answered with 500 Internal Server Error: {}", source))]
+        Service { source: BoxError },
+
+        #[snafu(display(
+            "Service panicked, answered with 500 Internal Server Error: {}",
+            message
+        ))]
+        Panic { message: String },
+
+        #[snafu(display(
+            "Request body wasn't read within {:?}, answered with 408 Request Timeout",
+            timeout
+        ))]
+        BodyTimeout { timeout: Duration },
+
+        #[snafu(display(
+            "Service didn't respond within {:?}, answered with 504 Gateway Timeout",
+            timeout
+        ))]
+        Timeout { timeout: Duration },
     }
 }
 
 pub use error::CgiServiceError;
+
+impl CgiServiceError {
+    /// Returns `true` if an error response was written before this error was returned.
+    pub fn response_written(&self) -> bool {
+        matches!(
+            self,
+            CgiServiceError::ResponseBodyCollect { .. }
+                | CgiServiceError::Service { .. }
+                | CgiServiceError::Panic { .. }
+                | CgiServiceError::Timeout { .. }
+                | CgiServiceError::BodyTimeout { .. }
+        )
+    }
+}
 type Result<T> = std::result::Result<T, CgiServiceError>;
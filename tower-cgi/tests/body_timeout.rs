@@ -0,0 +1,95 @@
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use axum::{routing::post, Router};
+use tower_cgi::{CgiServer, CgiServiceError, ServeStats};
+
+// Set when this test binary is run as the CGI process, to the name of the test to serve the request for.
+const CHILD: &str = "TOWER_CGI_BODY_TIMEOUT_CHILD";
+
+// Serves the request from the real environment and stdin, returning the result once the runtime has shut down.
+fn serve() -> Result<ServeStats, CgiServiceError> {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime.block_on(async {
+        let app = Router::new().route("/", post(|body: String| async move { body.to_uppercase() }));
+        CgiServer::new(app)
+            .with_body_timeout(Duration::from_millis(200))
+            .serve()
+            .await
+    });
+    // Shutting the runtime down must not wait for a read of stdin.
+    drop(runtime);
+    result
+}
+
+// Runs `test` in a CGI process of its own, whose stdin the caller writes to.
+fn spawn(test: &str, content_length: usize) -> Child {
+    Command::new(std::env::current_exe().unwrap())
+        .args([test, "--exact", "--test-threads=1"])
+        .env(CHILD, test)
+        .env("REQUEST_METHOD", "POST")
+        .env("REQUEST_URI", "/")
+        .env("CONTENT_LENGTH", content_length.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+// Waits up to 3s for `child` to exit, returning its stdout.
+fn wait(mut child: Child) -> String {
+    let deadline = Instant::now() + Duration::from_secs(3);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("the CGI process was still running after 3s");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    let mut output = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    assert!(status.success(), "{}", output);
+    output
+}
+
+#[test]
+fn reads_the_body_from_stdin() {
+    if std::env::var_os(CHILD).is_some() {
+        assert_eq!(serve().unwrap().request_body_len, 5);
+        return;
+    }
+
+    let mut child = spawn("reads_the_body_from_stdin", 5);
+    child.stdin.take().unwrap().write_all(b"hello").unwrap();
+    let output = wait(child);
+    assert!(output.contains("Status: 200 OK\r\n"), "{}", output);
+    assert!(output.contains("\r\n\r\nHELLO"), "{}", output);
+}
+
+#[test]
+fn body_timeout_ends_the_process_while_stdin_is_open() {
+    if std::env::var_os(CHILD).is_some() {
+        assert!(matches!(serve(), Err(CgiServiceError::BodyTimeout { .. })));
+        return;
+    }
+
+    let mut child = spawn("body_timeout_ends_the_process_while_stdin_is_open", 1024);
+    // The client never sends its body, nor closes the connection.
+    let _stdin = child.stdin.take();
+    let output = wait(child);
+    assert!(
+        output.contains("Status: 408 Request Timeout\r\n"),
+        "{}",
+        output
+    );
+}